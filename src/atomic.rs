@@ -0,0 +1,84 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Replaces the file at `path` with the output of `write` atomically: the output is written
+/// to a temporary file in the same directory, synced to disk and renamed over `path`.
+/// Readers see either the old or the new content, also after a crash.
+pub(crate) fn write_atomic(
+    path: impl AsRef<Path>,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let temp_path = temp_path(path)?;
+    let result = write_synced(&temp_path, write).and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+    sync_dir(path);
+    Ok(())
+}
+
+fn write_synced(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    writer
+        .into_inner()
+        .map_err(|error| error.into_error())?
+        .sync_all()
+}
+
+/// Unique hidden path next to `path`, so that the rename does not cross file systems
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(path.with_file_name(temp_name))
+}
+
+/// Persists the directory entry of `path` where the platform supports syncing directories
+pub(crate) fn sync_dir(path: &Path) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[test]
+fn atomic_write_replaces_content() {
+    use std::io::Write;
+    let dir = std::env::temp_dir().join(format!("p2p_node_stats_atomic_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("report.txt");
+    write_atomic(&path, |file| file.write_all(b"old")).unwrap();
+    let failed = write_atomic(&path, |file| {
+        file.write_all(b"partial")?;
+        Err(io::Error::other("crash"))
+    });
+    assert!(failed.is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+    write_atomic(&path, |file| file.write_all(b"new")).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    let n_files = fs::read_dir(&dir).unwrap().count();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(n_files, 1);
+}
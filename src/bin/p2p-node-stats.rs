@@ -0,0 +1,599 @@
+//! Inspects, converts and compares files saved by `p2p_node_stats`.
+//!
+//! Reads JSON or binary snapshots, snapshot journals, state files and binary stats.
+
+use p2p_node_stats::{PeerSnapshot, ReportFormat, Stats, StatsSnapshot};
+use std::{
+    cmp::Ordering, convert::TryInto, env, fmt::Write as _, fs, process::ExitCode, time::Duration,
+};
+
+const USAGE: &str = "\
+Usage:
+  p2p-node-stats show <file> [--sort peer|rtt|loss|rate] [--reverse] [--top <n>] [--match <pattern>]
+  p2p-node-stats convert <input> <output> --to <format>
+  p2p-node-stats diff <old> <new> [--threshold <percent>] [--match <pattern>]
+
+Tables list the worst peers first: slowest round trip, highest loss or lowest rate.
+Patterns match peer ids with `*` and `?` wildcards, or as a substring without them.
+Formats: json, binary, csv and samples-csv, as well as text and prometheus for
+state files and binary stats.";
+
+/// Relative change of the mean round trip time reported as slower or faster by default
+const DEFAULT_THRESHOLD_PERCENT: f64 = 10.0;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(Error::Usage(message)) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            ExitCode::from(2)
+        }
+        Err(Error::Failed(message)) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Error {
+    Usage(String),
+    Failed(String),
+}
+
+fn usage(message: impl Into<String>) -> Error {
+    Error::Usage(message.into())
+}
+
+fn failed(message: impl Into<String>) -> Error {
+    Error::Failed(message.into())
+}
+
+fn run(args: &[String]) -> Result<String, Error> {
+    let (command, args) = args.split_first().ok_or_else(|| usage("Missing command"))?;
+    let args = Args::parse(args)?;
+    match command.as_str() {
+        "show" => {
+            args.known_options(&["sort", "reverse", "top", "match"])?;
+            let [file] = args.positional::<1>()?;
+            let sort = args
+                .option("sort")
+                .map(str::parse)
+                .transpose()?
+                .unwrap_or(SortKey::Rtt);
+            let top = args
+                .option("top")
+                .map(|n| {
+                    n.parse::<usize>()
+                        .map_err(|_| usage(format!("Invalid number {:?}", n)))
+                })
+                .transpose()?;
+            let snapshot = load(file)?.snapshot();
+            Ok(show(
+                &snapshot,
+                sort,
+                args.flag("reverse"),
+                top,
+                args.option("match"),
+            ))
+        }
+        "convert" => {
+            args.known_options(&["to"])?;
+            let [input, output] = args.positional::<2>()?;
+            let format = args
+                .option("to")
+                .ok_or_else(|| usage("Missing --to <format>"))?
+                .parse::<ReportFormat>()
+                .map_err(usage)?;
+            load(input)?.save_as(output, format)?;
+            Ok(String::new())
+        }
+        "diff" => {
+            args.known_options(&["threshold", "match"])?;
+            let [old, new] = args.positional::<2>()?;
+            let threshold = match args.option("threshold") {
+                Some(percent) => percent
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|percent| *percent >= 0.0)
+                    .ok_or_else(|| usage(format!("Invalid threshold {:?}", percent)))?,
+                None => DEFAULT_THRESHOLD_PERCENT,
+            };
+            let (old, new) = (load(old)?.snapshot(), load(new)?.snapshot());
+            if old.node_id != new.node_id {
+                return Err(failed(format!(
+                    "Snapshots are of different nodes {:?} and {:?}",
+                    old.node_id, new.node_id
+                )));
+            }
+            Ok(diff(&old, &new, threshold / 100.0, args.option("match")))
+        }
+        "help" | "--help" | "-h" => Ok(format!("{}\n", USAGE)),
+        _ => Err(usage(format!("Unknown command {:?}", command))),
+    }
+}
+
+/// Positional arguments, `--name value` options and `--name` flags
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    const FLAGS: [&'static str; 1] = ["reverse"];
+
+    fn parse(args: &[String]) -> Result<Self, Error> {
+        let mut parsed = Self {
+            positional: Vec::new(),
+            options: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if Self::FLAGS.contains(&name) => {
+                    parsed.options.push((name.to_string(), None))
+                }
+                Some(name) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| usage(format!("Missing value of --{}", name)))?;
+                    parsed.options.push((name.to_string(), Some(value.clone())));
+                }
+                None => parsed.positional.push(arg.clone()),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn positional<const N: usize>(&self) -> Result<[&str; N], Error> {
+        let positional: Vec<&str> = self.positional.iter().map(String::as_str).collect();
+        positional.try_into().map_err(|positional: Vec<&str>| {
+            usage(format!(
+                "Expected {} file arguments, got {}",
+                N,
+                positional.len()
+            ))
+        })
+    }
+
+    /// Fails on the first option which is not one of `known`, e.g. a misspelled one
+    fn known_options(&self, known: &[&str]) -> Result<(), Error> {
+        match self
+            .options
+            .iter()
+            .find(|(option, _)| !known.contains(&option.as_str()))
+        {
+            Some((option, _)) => Err(usage(format!("Unknown option --{}", option))),
+            None => Ok(()),
+        }
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| option == name)
+    }
+}
+
+/// Contents of a saved file, full stats if the file has the raw samples with their timestamps
+enum Loaded {
+    Stats(Box<Stats>),
+    Snapshot(StatsSnapshot),
+}
+
+impl Loaded {
+    fn snapshot(&self) -> StatsSnapshot {
+        match self {
+            Loaded::Stats(stats) => stats.snapshot(true),
+            Loaded::Snapshot(snapshot) => snapshot.clone(),
+        }
+    }
+
+    fn save_as(&self, filename: &str, format: ReportFormat) -> Result<(), Error> {
+        match self {
+            Loaded::Stats(stats) => stats.save_as(filename, format),
+            Loaded::Snapshot(snapshot) => snapshot.save_as(filename, format),
+        }
+        .map_err(|error| failed(format!("Failed to write {}: {}", filename, error)))
+    }
+}
+
+fn load(filename: &str) -> Result<Loaded, Error> {
+    let failed_to_load =
+        |error: &dyn std::fmt::Display| failed(format!("Failed to load {}: {}", filename, error));
+    let bytes = fs::read(filename).map_err(|error| failed_to_load(&error))?;
+    if bytes.starts_with(b"P2PS") {
+        return match StatsSnapshot::decode_binary(&bytes) {
+            Ok(snapshot) => Ok(Loaded::Snapshot(snapshot)),
+            Err(_) => Stats::decode_binary_all(&bytes)
+                .map(|stats| Loaded::Stats(Box::new(stats)))
+                .map_err(|error| failed_to_load(&error)),
+        };
+    }
+    if bytes.starts_with(b"p2p-node-stats-state ") {
+        return Stats::load_all_from_file(filename)
+            .map(|stats| Loaded::Stats(Box::new(stats)))
+            .map_err(|error| failed_to_load(&error));
+    }
+    let text = String::from_utf8(bytes).map_err(|error| failed_to_load(&error))?;
+    match StatsSnapshot::from_json(&text) {
+        Ok(snapshot) => Ok(Loaded::Snapshot(snapshot)),
+        // A journal holds one snapshot per line, the last one is the latest
+        Err(error) => match StatsSnapshot::read_journal(filename) {
+            Ok(mut snapshots) if !snapshots.is_empty() => {
+                Ok(Loaded::Snapshot(snapshots.pop().expect("Journal is empty")))
+            }
+            _ => Err(failed_to_load(&error)),
+        },
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortKey {
+    Peer,
+    Rtt,
+    Loss,
+    Rate,
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = Error;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        match key {
+            "peer" => Ok(SortKey::Peer),
+            "rtt" => Ok(SortKey::Rtt),
+            "loss" => Ok(SortKey::Loss),
+            "rate" => Ok(SortKey::Rate),
+            _ => Err(usage(format!("Unknown sort key {:?}", key))),
+        }
+    }
+}
+
+/// Orders peers from the worst to the best by `key`, with unknown values last
+fn compare(key: SortKey, a: &PeerSnapshot, b: &PeerSnapshot) -> Ordering {
+    fn worst_first<T: PartialOrd>(a: Option<T>, b: Option<T>, worse: Ordering) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) => match a.partial_cmp(&b).unwrap_or(Ordering::Equal) {
+                Ordering::Equal => Ordering::Equal,
+                ordering if ordering == worse => Ordering::Less,
+                _ => Ordering::Greater,
+            },
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+    let ordering = match key {
+        SortKey::Peer => Ordering::Equal,
+        SortKey::Rtt => worst_first(
+            a.ping.as_ref().map(|ping| ping.mean),
+            b.ping.as_ref().map(|ping| ping.mean),
+            Ordering::Greater,
+        ),
+        SortKey::Loss => worst_first(
+            a.loss.as_ref().map(|loss| loss.rate),
+            b.loss.as_ref().map(|loss| loss.rate),
+            Ordering::Greater,
+        ),
+        SortKey::Rate => worst_first(
+            a.transmission
+                .as_ref()
+                .map(|transmission| transmission.mean),
+            b.transmission
+                .as_ref()
+                .map(|transmission| transmission.mean),
+            Ordering::Less,
+        ),
+    };
+    ordering.then_with(|| a.peer_id.cmp(&b.peer_id))
+}
+
+fn show(
+    snapshot: &StatsSnapshot,
+    sort: SortKey,
+    reverse: bool,
+    top: Option<usize>,
+    pattern: Option<&str>,
+) -> String {
+    let mut peers: Vec<&PeerSnapshot> = snapshot
+        .peers
+        .iter()
+        .filter(|peer| is_selected(pattern, &peer.peer_id))
+        .collect();
+    peers.sort_by(|a, b| compare(sort, a, b));
+    if reverse {
+        peers.reverse();
+    }
+    peers.truncate(top.unwrap_or(peers.len()));
+
+    let mut rows = vec![[
+        "PEER", "RTT MEAN", "ERROR", "MEDIAN", "JITTER", "PINGS", "LOSS", "RATE",
+    ]
+    .map(String::from)
+    .to_vec()];
+    for peer in peers {
+        let ping = peer.ping.as_ref();
+        rows.push(vec![
+            peer.peer_id.clone(),
+            format_option(ping.map(|ping| ping.mean), format_duration),
+            format_option(ping.and_then(|ping| ping.error), |error| {
+                format!("±{}", format_duration(error))
+            }),
+            format_option(ping.map(|ping| ping.median), format_duration),
+            format_option(ping.and_then(|ping| ping.jitter), format_duration),
+            format_option(ping.map(|ping| ping.n_samples), |n| n.to_string()),
+            format_option(peer.loss.as_ref(), |loss| {
+                format!("{:.1}%", loss.rate * 100.0)
+            }),
+            format_option(peer.transmission.as_ref(), |transmission| {
+                format!("{:.1} B/s", transmission.mean)
+            }),
+        ]);
+    }
+    format!("{:?}\n{}", snapshot.node_id, table(&rows))
+}
+
+fn diff(old: &StatsSnapshot, new: &StatsSnapshot, threshold: f64, pattern: Option<&str>) -> String {
+    let selected = |peer: &&PeerSnapshot| is_selected(pattern, &peer.peer_id);
+    let mut rows = vec![["PEER", "OLD RTT", "NEW RTT", "CHANGE", ""]
+        .map(String::from)
+        .to_vec()];
+    let (mut n_slower, mut n_faster) = (0, 0);
+    for new_peer in new.peers.iter().filter(selected) {
+        let old_mean = old
+            .peer(&new_peer.peer_id)
+            .and_then(|peer| peer.ping.as_ref())
+            .map(|ping| ping.mean);
+        let new_mean = new_peer.ping.as_ref().map(|ping| ping.mean);
+        let (change, verdict) = match (old_mean, new_mean) {
+            (Some(old_mean), Some(new_mean)) if !old_mean.is_zero() => {
+                let change = new_mean.as_secs_f64() / old_mean.as_secs_f64() - 1.0;
+                let verdict = if change > threshold {
+                    n_slower += 1;
+                    "slower"
+                } else if change < -threshold {
+                    n_faster += 1;
+                    "faster"
+                } else {
+                    ""
+                };
+                (format!("{:+.1}%", change * 100.0), verdict)
+            }
+            (None, _) if old.peer(&new_peer.peer_id).is_none() => (String::new(), "new"),
+            _ => (String::new(), ""),
+        };
+        rows.push(vec![
+            new_peer.peer_id.clone(),
+            format_option(old_mean, format_duration),
+            format_option(new_mean, format_duration),
+            change,
+            verdict.to_string(),
+        ]);
+    }
+    for old_peer in old.peers.iter().filter(selected) {
+        if new.peer(&old_peer.peer_id).is_none() {
+            rows.push(vec![
+                old_peer.peer_id.clone(),
+                format_option(
+                    old_peer.ping.as_ref().map(|ping| ping.mean),
+                    format_duration,
+                ),
+                "-".to_string(),
+                String::new(),
+                "gone".to_string(),
+            ]);
+        }
+    }
+    format!(
+        "{:?}\n{}{} slower, {} faster by more than {}%\n",
+        new.node_id,
+        table(&rows),
+        n_slower,
+        n_faster,
+        threshold * 100.0
+    )
+}
+
+/// Left-aligned columns separated by two spaces
+fn table(rows: &[Vec<String>]) -> String {
+    let n_columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..n_columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut table = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(&widths) {
+            let _ = write!(line, "{:width$}  ", cell, width = width);
+        }
+        let _ = writeln!(table, "{}", line.trim_end());
+    }
+    table
+}
+
+fn format_option<T>(value: Option<T>, format: impl FnOnce(T) -> String) -> String {
+    value.map(format).unwrap_or_else(|| "-".to_string())
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.2?}", duration)
+}
+
+/// Every peer is selected without a pattern
+fn is_selected(pattern: Option<&str>, peer_id: &str) -> bool {
+    match pattern {
+        Some(pattern) => matches_pattern(pattern, peer_id),
+        None => true,
+    }
+}
+
+/// Matches `text` against a pattern with `*` for any characters and `?` for one character,
+/// or checks that `text` contains the pattern if it has no wildcards
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return text.contains(pattern);
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it is currently matched up to
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn correct_matches() {
+    assert!(matches_pattern("peer", "node-peer-1"));
+    assert!(matches_pattern("node-*", "node-peer-1"));
+    assert!(matches_pattern("*-?", "node-peer-1"));
+    assert!(matches_pattern("*peer*1", "node-peer-1"));
+    assert!(!matches_pattern("node-?", "node-peer-1"));
+    assert!(!matches_pattern("*2", "node-peer-1"));
+    assert!(matches_pattern("*", ""));
+}
+
+#[test]
+fn show_lists_slowest_peers_first() {
+    let stats = Stats::new(100, "1".to_string());
+    stats.add_ping("fast".to_string(), Duration::from_millis(10));
+    stats.add_ping("slow".to_string(), Duration::from_millis(300));
+    stats.add_ping("medium".to_string(), Duration::from_millis(50));
+    stats.add_ping_timeout("medium".to_string());
+    let path = env::temp_dir().join(format!("p2p_node_stats_cli_show_{}", std::process::id()));
+    let filename = path.to_str().unwrap();
+    stats.snapshot(false).save_json(filename).unwrap();
+
+    let output = run(&args(&["show", filename, "--top", "2"])).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "\"1\"");
+    assert!(lines[1].starts_with("PEER    RTT MEAN"));
+    assert!(lines[2].starts_with("slow    300.00ms"));
+    assert!(lines[3].starts_with("medium  50.00ms"));
+    assert_eq!(lines.len(), 4);
+
+    let output = run(&args(&[
+        "show", filename, "--sort", "loss", "--match", "f*",
+    ]))
+    .unwrap();
+    fs::remove_file(filename).unwrap();
+    assert_eq!(output.lines().count(), 3);
+    assert!(output.contains("\nfast "));
+    assert!(matches!(
+        run(&args(&["show", filename, "--sort", "size"])),
+        Err(Error::Usage(_))
+    ));
+}
+
+#[test]
+fn convert_and_diff_snapshots() {
+    let dir = env::temp_dir().join(format!("p2p_node_stats_cli_diff_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let stats = Stats::new(100, "1".to_string());
+    stats.add_ping("2".to_string(), Duration::from_millis(100));
+    stats.add_ping("3".to_string(), Duration::from_millis(100));
+    stats.add_ping("4".to_string(), Duration::from_millis(100));
+    stats.save_state_to_file(&file("old.state")).unwrap();
+    run(&args(&[
+        "convert",
+        &file("old.state"),
+        &file("old.bin"),
+        "--to",
+        "binary",
+    ]))
+    .unwrap();
+    run(&args(&[
+        "convert",
+        &file("old.bin"),
+        &file("old.json"),
+        "--to",
+        "json",
+    ]))
+    .unwrap();
+
+    stats.add_ping("2".to_string(), Duration::from_millis(200));
+    stats.add_ping("3".to_string(), Duration::from_millis(20));
+    stats.remove_peer("4");
+    stats.add_ping("5".to_string(), Duration::from_millis(100));
+    stats
+        .append_to_journal(&file("new.jsonl"), 1 << 20)
+        .unwrap();
+
+    let output = run(&args(&["diff", &file("old.json"), &file("new.jsonl")])).unwrap();
+    let converted = run(&args(&[
+        "convert",
+        &file("new.jsonl"),
+        &file("new.txt"),
+        "--to",
+        "text",
+    ]));
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.contains("\n2     100.00ms  150.00ms  +50.0%  slower\n"));
+    assert!(output.contains("\n3     100.00ms  60.00ms   -40.0%  faster\n"));
+    assert!(output.contains("\n5     -         100.00ms          new\n"));
+    assert!(output.contains("\n4     100.00ms  -                 gone\n"));
+    assert!(output.ends_with("1 slower, 1 faster by more than 10%\n"));
+    assert!(matches!(converted, Err(Error::Failed(_))));
+}
+
+#[test]
+fn shows_old_state_files() {
+    use std::time::Instant;
+
+    let stats = Stats::new(100, "1".to_string()).with_max_age(Duration::from_secs(60));
+    let Some(long_ago) = Instant::now().checked_sub(Duration::from_secs(120)) else {
+        return;
+    };
+    stats.add_ping_at("2".to_string(), Duration::from_millis(100), long_ago);
+    let path = env::temp_dir().join(format!("p2p_node_stats_cli_old_{}", std::process::id()));
+    let filename = path.to_str().unwrap();
+    stats.save_state_to_file(filename).unwrap();
+
+    let output = run(&args(&["show", filename]));
+    let misspelled = run(&args(&["show", filename, "--tpo", "3"]));
+    fs::remove_file(filename).unwrap();
+    assert!(output.unwrap().contains("\n2     100.00ms"));
+    assert!(matches!(misspelled, Err(Error::Usage(_))));
+}
@@ -0,0 +1,783 @@
+//! Compact binary encoding of stats, for frequent dumps from many nodes.
+//!
+//! A record starts with the magic bytes `P2PS`, the format version and the record kind,
+//! followed by a table of the interned node and peer ids. Integers are LEB128 varints,
+//! sequences of durations and timestamps are delta encoded with zigzag varints.
+
+use crate::{
+    state::{parse_peer_id, saturating_instant_sub},
+    upsert_peer, ChangePoint, ConfidenceLevel, JitterEstimator, LossSummary, Metric,
+    PeerIdentifier, PeerSnapshot, RttEstimator, Sample, Stats, StatsSnapshot, Summary,
+    ThroughputSummary, Transmission, Window,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: &[u8; 4] = b"P2PS";
+const VERSION: u8 = 1;
+
+const KIND_SNAPSHOT: u8 = 0;
+const KIND_STATS: u8 = 1;
+
+const HAS_PINGS: u8 = 1;
+const HAS_OUTCOMES: u8 = 1 << 1;
+const HAS_TRANSMISSIONS: u8 = 1 << 2;
+const HAS_TIMESTAMPS: u8 = 1 << 3;
+
+/// Raw samples of a peer, with timestamps when encoding [`Stats`]
+#[derive(Default)]
+struct PeerWindows {
+    pings: Option<Vec<Duration>>,
+    outcomes: Option<Vec<bool>>,
+    transmissions: Option<Vec<Transmission>>,
+    taken_at: Option<[Vec<SystemTime>; 3]>,
+}
+
+impl StatsSnapshot {
+    /// Encodes the snapshot in the compact binary format, see [`StatsSnapshot::decode_binary`]
+    pub fn encode_binary(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(
+            KIND_SNAPSHOT,
+            std::iter::once(&self.node_id).chain(self.peers.iter().map(|peer| &peer.peer_id)),
+        );
+        encoder.string(&self.node_id);
+        encoder.timestamp(self.taken_at);
+        encoder.varint(self.peers.len() as u64);
+        for peer in &self.peers {
+            encoder.string(&peer.peer_id);
+            encoder.windows(&PeerWindows {
+                pings: peer.pings.clone(),
+                transmissions: peer.transmissions.clone(),
+                ..PeerWindows::default()
+            });
+            encoder.option(peer.ping.as_ref(), Encoder::summary);
+            encoder.option(peer.transmission.as_ref(), Encoder::throughput);
+            encoder.option(peer.loss.as_ref(), Encoder::loss);
+            encoder.varint(peer.change_points.len() as u64);
+            for change_point in &peer.change_points {
+                encoder.change_point(change_point);
+            }
+        }
+        encoder.bytes
+    }
+
+    /// Decodes a snapshot encoded by [`StatsSnapshot::encode_binary`]
+    pub fn decode_binary(bytes: &[u8]) -> io::Result<Self> {
+        let mut decoder = Decoder::new(bytes, KIND_SNAPSHOT)?;
+        let node_id = decoder.string()?;
+        let taken_at = decoder.timestamp()?;
+        let n_peers = decoder.len()?;
+        let mut peers = Vec::with_capacity(n_peers);
+        for _ in 0..n_peers {
+            let peer_id = decoder.string()?;
+            let windows = decoder.windows()?;
+            let ping = decoder.option(Decoder::summary)?;
+            let transmission = decoder.option(Decoder::throughput)?;
+            let loss = decoder.option(Decoder::loss)?;
+            let n_change_points = decoder.len()?;
+            let change_points = (0..n_change_points)
+                .map(|_| decoder.change_point())
+                .collect::<io::Result<_>>()?;
+            peers.push(PeerSnapshot {
+                peer_id,
+                ping,
+                transmission,
+                loss,
+                pings: windows.pings,
+                transmissions: windows.transmissions,
+                change_points,
+            });
+        }
+        decoder.finish()?;
+        Ok(Self {
+            node_id,
+            taken_at,
+            peers,
+        })
+    }
+}
+
+impl<P: PeerIdentifier> Stats<P> {
+    /// Encodes the settings and the raw samples of all windows in the compact binary format.
+    /// Summaries are not encoded, as they are computed from the samples.
+    pub fn encode_binary(&self) -> Vec<u8> {
+        self.prune();
+        let now = Instant::now();
+        let mut peers: HashMap<String, PeerWindows> = HashMap::new();
+        for (peer, mut window) in self.pings_to_peers.clone() {
+            window.expire(now);
+            let windows = peers.entry(peer.to_string()).or_default();
+            windows.pings = Some(window.to_vec());
+            windows.taken_at.get_or_insert_with(Default::default)[0] = taken_at(&window);
+        }
+        for (peer, mut window) in self.ping_outcomes.clone() {
+            window.expire(now);
+            let windows = peers.entry(peer.to_string()).or_default();
+            windows.outcomes = Some(window.to_vec());
+            windows.taken_at.get_or_insert_with(Default::default)[1] = taken_at(&window);
+        }
+        for (peer, mut window) in self.transmissions_rates.clone() {
+            window.expire(now);
+            let windows = peers.entry(peer.to_string()).or_default();
+            windows.transmissions = Some(window.to_vec());
+            windows.taken_at.get_or_insert_with(Default::default)[2] = taken_at(&window);
+        }
+        let mut peers: Vec<(String, PeerWindows)> = peers.into_iter().collect();
+        peers.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let node_id = self.peer_id.to_string();
+        let mut encoder = Encoder::new(
+            KIND_STATS,
+            std::iter::once(&node_id).chain(peers.iter().map(|(peer, _)| peer)),
+        );
+        encoder.string(&node_id);
+        encoder.timestamp(SystemTime::now());
+        encoder.varint(self.window_size as u64);
+        encoder.option(self.max_age.as_ref(), |encoder, &max_age| {
+            encoder.duration(max_age)
+        });
+        encoder.varint(peers.len() as u64);
+        for (peer, windows) in &peers {
+            encoder.string(peer);
+            encoder.windows(windows);
+        }
+        encoder.bytes
+    }
+}
+
+impl<P: PeerIdentifier + FromStr> Stats<P> {
+    /// Restores stats encoded by [`Stats::encode_binary`]. Round trip time and jitter
+    /// estimators are rebuilt from the pings in the windows, other settings should be set again.
+    pub fn decode_binary(bytes: &[u8]) -> io::Result<Self> {
+        Self::decode_stats(bytes, true)
+    }
+
+    /// Restores stats like [`Stats::decode_binary`], but ignores the encoded max age so that
+    /// no sample expires, however old the encoding is. Useful to inspect a saved node.
+    pub fn decode_binary_all(bytes: &[u8]) -> io::Result<Self> {
+        Self::decode_stats(bytes, false)
+    }
+
+    fn decode_stats(bytes: &[u8], keep_max_age: bool) -> io::Result<Self> {
+        let mut decoder = Decoder::new(bytes, KIND_STATS)?;
+        let node_id = decoder.string()?;
+        let encoded_at = decoder.timestamp()?;
+        let window_size = decoder.varint()? as usize;
+        let max_age = decoder.option(Decoder::duration)?;
+        let mut stats = Self::new(window_size, parse_peer_id(&node_id)?);
+        stats.max_age = max_age.filter(|_| keep_max_age);
+
+        let now = Instant::now();
+        let wall_now = SystemTime::now().max(encoded_at);
+        let instant = |taken_at: SystemTime| {
+            saturating_instant_sub(now, wall_now.duration_since(taken_at).unwrap_or_default())
+        };
+        let n_peers = decoder.len()?;
+        let mut peers = HashSet::new();
+        for _ in 0..n_peers {
+            let peer: P = parse_peer_id(&decoder.string()?)?;
+            // Distinct strings may parse to the same id, e.g. `1` and `01` for numeric ids
+            if !peers.insert(peer.clone()) {
+                return Err(invalid_data("Duplicate peer in binary stats"));
+            }
+            let windows = decoder.windows()?;
+            let [pings_taken_at, outcomes_taken_at, transmissions_taken_at] =
+                windows.taken_at.unwrap_or_default();
+            if let Some(pings) = windows.pings {
+                for (&rtt, &taken_at) in pings.iter().zip(&pings_taken_at) {
+                    upsert_peer(
+                        &stats.rtt_estimators,
+                        &peer,
+                        || RttEstimator::new(rtt),
+                        |estimator| estimator.update(rtt),
+                    );
+                    upsert_peer(
+                        &stats.jitter_estimators,
+                        &peer,
+                        || JitterEstimator::new(rtt),
+                        |estimator| estimator.update(rtt),
+                    );
+                    stats.touch_peer(&peer, instant(taken_at));
+                }
+                let window = stats.restore_samples(pings, pings_taken_at, instant);
+                stats.pings_to_peers.insert_new(peer.clone(), window);
+            }
+            if let Some(outcomes) = windows.outcomes {
+                if let Some(&taken_at) = outcomes_taken_at.last() {
+                    stats.touch_peer(&peer, instant(taken_at));
+                }
+                let window = stats.restore_samples(outcomes, outcomes_taken_at, instant);
+                stats.ping_outcomes.insert_new(peer.clone(), window);
+            }
+            if let Some(transmissions) = windows.transmissions {
+                if let Some(&taken_at) = transmissions_taken_at.last() {
+                    stats.touch_peer(&peer, instant(taken_at));
+                }
+                let window = stats.restore_samples(transmissions, transmissions_taken_at, instant);
+                stats.transmissions_rates.insert_new(peer, window);
+            }
+        }
+        decoder.finish()?;
+        Ok(stats)
+    }
+
+    fn restore_samples<T>(
+        &self,
+        values: Vec<T>,
+        taken_at: Vec<SystemTime>,
+        instant: impl Fn(SystemTime) -> Instant,
+    ) -> Window<T> {
+        let mut window = self.new_window();
+        for (value, taken_at) in values.into_iter().zip(taken_at) {
+            window.push_sample(Sample {
+                value,
+                at: instant(taken_at),
+                taken_at,
+            });
+        }
+        window.expire(Instant::now());
+        window
+    }
+}
+
+fn taken_at<T>(window: &Window<T>) -> Vec<SystemTime> {
+    window.samples().map(|sample| sample.taken_at).collect()
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+    string_ids: HashMap<String, u64>,
+}
+
+impl Encoder {
+    /// Starts a record of `kind` with the table of `strings`, which are later referenced by index
+    fn new<'a>(kind: u8, strings: impl Iterator<Item = &'a String>) -> Self {
+        let mut encoder = Self {
+            bytes: MAGIC.to_vec(),
+            string_ids: HashMap::new(),
+        };
+        encoder.bytes.extend([VERSION, kind]);
+        let mut table = Vec::new();
+        for string in strings {
+            if !encoder.string_ids.contains_key(string) {
+                encoder
+                    .string_ids
+                    .insert(string.clone(), table.len() as u64);
+                table.push(string);
+            }
+        }
+        encoder.varint(table.len() as u64);
+        for string in table {
+            encoder.varint(string.len() as u64);
+            encoder.bytes.extend_from_slice(string.as_bytes());
+        }
+        encoder
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn signed_varint(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes())
+    }
+
+    fn string(&mut self, string: &str) {
+        let id = self.string_ids[string];
+        self.varint(id)
+    }
+
+    fn duration(&mut self, duration: Duration) {
+        self.varint(duration.as_nanos() as u64)
+    }
+
+    /// Nanoseconds since the Unix epoch
+    fn timestamp(&mut self, time: SystemTime) {
+        self.varint(nanos_since_epoch(time) as u64)
+    }
+
+    fn option<T>(&mut self, value: Option<&T>, encode: impl FnOnce(&mut Self, &T)) {
+        match value {
+            Some(value) => {
+                self.bytes.push(1);
+                encode(self, value)
+            }
+            None => self.bytes.push(0),
+        }
+    }
+
+    fn confidence(&mut self, confidence: ConfidenceLevel) {
+        self.bytes
+            .push((confidence.fraction() * 100.0).round() as u8)
+    }
+
+    /// Each value as the difference to the previous one
+    fn deltas(&mut self, values: impl Iterator<Item = i64>) {
+        let mut previous = 0;
+        for value in values {
+            self.signed_varint(value.wrapping_sub(previous));
+            previous = value;
+        }
+    }
+
+    fn windows(&mut self, windows: &PeerWindows) {
+        let flags = [
+            (windows.pings.is_some(), HAS_PINGS),
+            (windows.outcomes.is_some(), HAS_OUTCOMES),
+            (windows.transmissions.is_some(), HAS_TRANSMISSIONS),
+            (windows.taken_at.is_some(), HAS_TIMESTAMPS),
+        ]
+        .iter()
+        .filter(|(present, _)| *present)
+        .fold(0, |flags, (_, flag)| flags | flag);
+        self.bytes.push(flags);
+        if let Some(pings) = &windows.pings {
+            self.varint(pings.len() as u64);
+            self.deltas(pings.iter().map(|rtt| rtt.as_nanos() as i64));
+        }
+        if let Some(outcomes) = &windows.outcomes {
+            self.varint(outcomes.len() as u64);
+            for chunk in outcomes.chunks(8) {
+                let bits = chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |bits, (i, &answered)| bits | (answered as u8) << i);
+                self.bytes.push(bits);
+            }
+        }
+        if let Some(transmissions) = &windows.transmissions {
+            self.varint(transmissions.len() as u64);
+            for transmission in transmissions {
+                self.varint(transmission.n_bytes);
+            }
+            self.deltas(
+                transmissions
+                    .iter()
+                    .map(|transmission| transmission.elapsed.as_nanos() as i64),
+            );
+        }
+        if let Some(taken_at) = &windows.taken_at {
+            let present = [
+                windows.pings.is_some(),
+                windows.outcomes.is_some(),
+                windows.transmissions.is_some(),
+            ];
+            for (times, _) in taken_at.iter().zip(present).filter(|(_, present)| *present) {
+                self.deltas(times.iter().map(|&time| nanos_since_epoch(time)));
+            }
+        }
+    }
+
+    fn summary(&mut self, summary: &Summary) {
+        self.duration(summary.mean);
+        self.duration(summary.std_dev);
+        self.option(summary.error.as_ref(), |encoder, &error| {
+            encoder.duration(error)
+        });
+        self.confidence(summary.confidence);
+        self.varint(summary.n_samples as u64);
+        self.varint(summary.n_outliers as u64);
+        self.duration(summary.min);
+        self.duration(summary.max);
+        self.duration(summary.median);
+        self.duration(summary.mad);
+        self.option(summary.jitter.as_ref(), |encoder, &jitter| {
+            encoder.duration(jitter)
+        });
+    }
+
+    fn throughput(&mut self, summary: &ThroughputSummary) {
+        self.f64(summary.mean);
+        self.f64(summary.std_dev);
+        self.option(summary.error.as_ref(), |encoder, &error| encoder.f64(error));
+        self.confidence(summary.confidence);
+        self.varint(summary.n_samples as u64);
+        self.varint(summary.n_outliers as u64);
+        self.varint(summary.n_bytes);
+        self.f64(summary.min);
+        self.f64(summary.max);
+        self.f64(summary.median);
+        self.f64(summary.mad);
+    }
+
+    fn loss(&mut self, loss: &LossSummary) {
+        self.varint(loss.n_sent as u64);
+        self.varint(loss.n_lost as u64);
+        self.f64(loss.rate);
+        self.f64(loss.ci_low);
+        self.f64(loss.ci_high);
+        self.confidence(loss.confidence);
+    }
+
+    fn change_point(&mut self, change_point: &ChangePoint) {
+        self.bytes.push(match change_point.metric {
+            Metric::Rtt => 0,
+            Metric::TransmissionRate => 1,
+        });
+        self.timestamp(change_point.at);
+        self.f64(change_point.before);
+        self.f64(change_point.after);
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    strings: Vec<String>,
+}
+
+impl<'a> Decoder<'a> {
+    /// Checks the header of a record of `kind` and reads its table of strings
+    fn new(bytes: &'a [u8], kind: u8) -> io::Result<Self> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(invalid_data("Not binary stats"));
+        }
+        let mut decoder = Self {
+            bytes,
+            position: MAGIC.len(),
+            strings: Vec::new(),
+        };
+        let version = decoder.u8()?;
+        if version > VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported binary stats version {}",
+                version
+            )));
+        }
+        if decoder.u8()? != kind {
+            return Err(invalid_data("Unexpected kind of binary stats"));
+        }
+        let n_strings = decoder.len()?;
+        for _ in 0..n_strings {
+            let len = decoder.len()?;
+            let string = String::from_utf8(decoder.take(len)?.to_vec())
+                .map_err(|_| invalid_data("Invalid UTF-8 in binary stats"))?;
+            decoder.strings.push(string);
+        }
+        Ok(decoder)
+    }
+
+    fn finish(self) -> io::Result<()> {
+        if self.position == self.bytes.len() {
+            Ok(())
+        } else {
+            Err(invalid_data("Trailing bytes after binary stats"))
+        }
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| invalid_data("Truncated binary stats"))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_data("Varint too long in binary stats"))
+    }
+
+    fn signed_varint(&mut self) -> io::Result<i64> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    /// Length of a sequence, which can not be longer than the remaining bytes
+    fn len(&mut self) -> io::Result<usize> {
+        let len = self.varint()?;
+        if len > (self.bytes.len() - self.position) as u64 * 8 {
+            return Err(invalid_data("Truncated binary stats"));
+        }
+        Ok(len as usize)
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let id = self.varint()?;
+        self.strings
+            .get(id as usize)
+            .cloned()
+            .ok_or_else(|| invalid_data("Invalid string reference in binary stats"))
+    }
+
+    fn duration(&mut self) -> io::Result<Duration> {
+        Ok(Duration::from_nanos(self.varint()?))
+    }
+
+    fn timestamp(&mut self) -> io::Result<SystemTime> {
+        Ok(UNIX_EPOCH + Duration::from_nanos(self.varint()?))
+    }
+
+    fn option<T>(
+        &mut self,
+        decode: impl FnOnce(&mut Self) -> io::Result<T>,
+    ) -> io::Result<Option<T>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => decode(self).map(Some),
+            _ => Err(invalid_data("Invalid option in binary stats")),
+        }
+    }
+
+    fn confidence(&mut self) -> io::Result<ConfidenceLevel> {
+        match self.u8()? {
+            90 => Ok(ConfidenceLevel::P90),
+            95 => Ok(ConfidenceLevel::P95),
+            99 => Ok(ConfidenceLevel::P99),
+            _ => Err(invalid_data("Invalid confidence level in binary stats")),
+        }
+    }
+
+    fn deltas(&mut self, len: usize) -> io::Result<Vec<i64>> {
+        let mut previous: i64 = 0;
+        (0..len)
+            .map(|_| {
+                previous = previous.wrapping_add(self.signed_varint()?);
+                Ok(previous)
+            })
+            .collect()
+    }
+
+    fn durations(&mut self, len: usize) -> io::Result<Vec<Duration>> {
+        Ok(self
+            .deltas(len)?
+            .into_iter()
+            .map(|nanos| Duration::from_nanos(nanos as u64))
+            .collect())
+    }
+
+    fn windows(&mut self) -> io::Result<PeerWindows> {
+        let flags = self.u8()?;
+        let mut windows = PeerWindows::default();
+        if flags & HAS_PINGS != 0 {
+            let len = self.len()?;
+            windows.pings = Some(self.durations(len)?);
+        }
+        if flags & HAS_OUTCOMES != 0 {
+            let len = self.len()?;
+            let bits = self.take(len.div_ceil(8))?;
+            windows.outcomes = Some((0..len).map(|i| bits[i / 8] >> (i % 8) & 1 == 1).collect());
+        }
+        if flags & HAS_TRANSMISSIONS != 0 {
+            let len = self.len()?;
+            let n_bytes = (0..len)
+                .map(|_| self.varint())
+                .collect::<io::Result<Vec<_>>>()?;
+            let elapsed = self.durations(len)?;
+            windows.transmissions = Some(
+                n_bytes
+                    .into_iter()
+                    .zip(elapsed)
+                    .map(|(n_bytes, elapsed)| Transmission { n_bytes, elapsed })
+                    .collect(),
+            );
+        }
+        if flags & HAS_TIMESTAMPS != 0 {
+            let lens = [
+                windows.pings.as_ref().map(Vec::len),
+                windows.outcomes.as_ref().map(Vec::len),
+                windows.transmissions.as_ref().map(Vec::len),
+            ];
+            let mut taken_at: [Vec<SystemTime>; 3] = Default::default();
+            for (times, len) in taken_at.iter_mut().zip(lens) {
+                if let Some(len) = len {
+                    *times = self
+                        .deltas(len)?
+                        .into_iter()
+                        .map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos as u64))
+                        .collect();
+                }
+            }
+            windows.taken_at = Some(taken_at);
+        }
+        Ok(windows)
+    }
+
+    fn summary(&mut self) -> io::Result<Summary> {
+        Ok(Summary {
+            mean: self.duration()?,
+            std_dev: self.duration()?,
+            error: self.option(Self::duration)?,
+            confidence: self.confidence()?,
+            n_samples: self.varint()? as usize,
+            n_outliers: self.varint()? as usize,
+            min: self.duration()?,
+            max: self.duration()?,
+            median: self.duration()?,
+            mad: self.duration()?,
+            jitter: self.option(Self::duration)?,
+        })
+    }
+
+    fn throughput(&mut self) -> io::Result<ThroughputSummary> {
+        Ok(ThroughputSummary {
+            mean: self.f64()?,
+            std_dev: self.f64()?,
+            error: self.option(Self::f64)?,
+            confidence: self.confidence()?,
+            n_samples: self.varint()? as usize,
+            n_outliers: self.varint()? as usize,
+            n_bytes: self.varint()?,
+            min: self.f64()?,
+            max: self.f64()?,
+            median: self.f64()?,
+            mad: self.f64()?,
+        })
+    }
+
+    fn loss(&mut self) -> io::Result<LossSummary> {
+        Ok(LossSummary {
+            n_sent: self.varint()? as usize,
+            n_lost: self.varint()? as usize,
+            rate: self.f64()?,
+            ci_low: self.f64()?,
+            ci_high: self.f64()?,
+            confidence: self.confidence()?,
+        })
+    }
+
+    fn change_point(&mut self) -> io::Result<ChangePoint> {
+        let metric = match self.u8()? {
+            0 => Metric::Rtt,
+            1 => Metric::TransmissionRate,
+            _ => return Err(invalid_data("Invalid metric in binary stats")),
+        };
+        Ok(ChangePoint {
+            metric,
+            at: self.timestamp()?,
+            before: self.f64()?,
+            after: self.f64()?,
+        })
+    }
+}
+
+fn nanos_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as i64
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[test]
+fn correct_varint() {
+    let mut encoder = Encoder::new(KIND_SNAPSHOT, std::iter::empty());
+    let values = [0, 1, 127, 128, 300, u64::MAX];
+    values.iter().for_each(|&value| encoder.varint(value));
+    let signed = [0, -1, 1, -64, 64, i64::MIN, i64::MAX];
+    signed
+        .iter()
+        .for_each(|&value| encoder.signed_varint(value));
+    assert_eq!(encoder.bytes[7..10], [0, 1, 127]);
+
+    let mut decoder = Decoder::new(&encoder.bytes, KIND_SNAPSHOT).unwrap();
+    for &value in &values {
+        assert_eq!(decoder.varint().unwrap(), value);
+    }
+    for &value in &signed {
+        assert_eq!(decoder.signed_varint().unwrap(), value);
+    }
+    decoder.finish().unwrap();
+}
+
+#[test]
+fn snapshot_binary_round_trip() {
+    let stats =
+        Stats::new(100, "1".to_string()).with_change_detection(crate::ChangeDetection::default());
+    for i in 0..40 {
+        let rtt = Duration::from_micros(if i < 20 { 10_000 } else { 30_000 } + i * 7);
+        stats.add_ping("2".to_string(), rtt);
+    }
+    stats.add_ping_timeout("2".to_string());
+    stats.add_transmission("3".to_string(), Duration::from_millis(250), 1000);
+    stats.add_transmission("3".to_string(), Duration::from_millis(120), 1500);
+
+    for include_raw in [false, true] {
+        let snapshot = stats.snapshot(include_raw);
+        let bytes = snapshot.encode_binary();
+        assert_eq!(StatsSnapshot::decode_binary(&bytes).unwrap(), snapshot);
+        assert!(bytes.len() < snapshot.to_json().unwrap().len() / 3);
+    }
+    let bytes = stats.snapshot(true).encode_binary();
+    for len in 0..bytes.len() {
+        assert!(StatsSnapshot::decode_binary(&bytes[..len]).is_err());
+    }
+    assert!(Stats::<String>::decode_binary(&bytes).is_err());
+}
+
+#[test]
+fn stats_binary_round_trip() {
+    let stats = Stats::new(3, "1".to_string()).with_max_age(Duration::from_secs(3600));
+    for millis in [10, 20, 30, 25] {
+        stats.add_ping("2".to_string(), Duration::from_millis(millis));
+    }
+    stats.add_ping_timeout("2".to_string());
+    stats.add_transmission("3".to_string(), Duration::from_secs(1), 1000);
+
+    let restored: Stats = Stats::decode_binary(&stats.encode_binary()).unwrap();
+    assert_eq!(restored.window_size, 3);
+    assert_eq!(restored.max_age, Some(Duration::from_secs(3600)));
+    assert_eq!(
+        restored.ping_summary("2").map(|s| s.mean),
+        stats.ping_summary("2").map(|s| s.mean)
+    );
+    assert_eq!(restored.ping_loss("2"), stats.ping_loss("2"));
+    assert_eq!(
+        restored.transmission_summary("3"),
+        stats.transmission_summary("3")
+    );
+    let window = |stats: &Stats| {
+        stats
+            .samples_between("2", UNIX_EPOCH, SystemTime::now() + Duration::from_secs(60))
+            .into_iter()
+            .map(|sample| (sample.value, sample.taken_at))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(window(&restored), window(&stats));
+    assert!(restored.last_seen("3").is_some());
+}
+
+#[test]
+fn rejects_duplicate_binary_peers() {
+    let stats = Stats::new(10, "1".to_string());
+    stats.add_ping("1".to_string(), Duration::from_millis(10));
+    stats.add_ping("01".to_string(), Duration::from_millis(20));
+    let error = Stats::<u32>::decode_binary(&stats.encode_binary())
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// Metric a change point was detected in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Metric {
+    /// Round trip time in seconds
+    Rtt,
+    /// Transmission rate in bytes per second
+    TransmissionRate,
+}
+
+impl Metric {
+    /// Formats a level of this metric with its unit
+    pub fn format_value(self, value: f64) -> String {
+        match self {
+            Metric::Rtt if value.is_finite() && value >= 0.0 => {
+                format!("{:?}", std::time::Duration::from_secs_f64(value))
+            }
+            Metric::Rtt => format!("{}s", value),
+            Metric::TransmissionRate => format!("{:.1} B/s", value),
+        }
+    }
+}
+
+impl std::fmt::Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Metric::Rtt => "rtt",
+            Metric::TransmissionRate => "transmission rate",
+        })
+    }
+}
+
+/// Shift of a metric to a new level
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChangePoint {
+    pub metric: Metric,
+    /// Time of the sample the shift was detected at
+    pub at: SystemTime,
+    /// Mean level before the shift
+    pub before: f64,
+    /// Mean level of the samples since the shift started
+    pub after: f64,
+}
+
+/// Parameters of the Page-Hinkley test, expressed relative to the current mean level
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChangeDetection {
+    /// Relative deviation from the mean tolerated without accumulating evidence of a shift
+    pub delta: f64,
+    /// Accumulated relative deviation at which a shift is reported
+    pub threshold: f64,
+    /// Samples used to learn the level before shifts are detected
+    pub min_samples: usize,
+}
+
+impl Default for ChangeDetection {
+    fn default() -> Self {
+        Self {
+            delta: 0.1,
+            threshold: 3.0,
+            min_samples: 10,
+        }
+    }
+}
+
+/// Two-sided Page-Hinkley change-point detector
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageHinkley {
+    config: ChangeDetection,
+    n_samples: usize,
+    mean: f64,
+    increase: Cusum,
+    decrease: Cusum,
+}
+
+/// Cumulative deviation with the samples observed since it was at its extreme
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Cusum {
+    sum: f64,
+    extreme: f64,
+    sum_since_extreme: f64,
+    n_since_extreme: usize,
+}
+
+impl Cusum {
+    /// Accumulates `deviation` and returns how far the sum moved away from its extreme,
+    /// `sign` being `1` when looking for increases and `-1` for decreases
+    fn update(&mut self, deviation: f64, value: f64, sign: f64) -> f64 {
+        self.sum += deviation;
+        if sign * self.sum <= sign * self.extreme {
+            self.extreme = self.sum;
+            self.sum_since_extreme = 0.0;
+            self.n_since_extreme = 0;
+        } else {
+            self.sum_since_extreme += value;
+            self.n_since_extreme += 1;
+        }
+        sign * (self.sum - self.extreme)
+    }
+}
+
+impl PageHinkley {
+    pub fn new(config: ChangeDetection) -> Self {
+        Self {
+            config,
+            n_samples: 0,
+            mean: 0.0,
+            increase: Cusum::default(),
+            decrease: Cusum::default(),
+        }
+    }
+
+    /// Feeds the next value, returning the levels before and after a detected shift
+    pub fn update(&mut self, value: f64) -> Option<(f64, f64)> {
+        self.n_samples += 1;
+        if self.n_samples <= self.config.min_samples || self.mean == 0.0 {
+            self.mean += (value - self.mean) / self.n_samples as f64;
+            return None;
+        }
+        let deviation = (value - self.mean) / self.mean.abs();
+        let increase = self
+            .increase
+            .update(deviation - self.config.delta, value, 1.0);
+        let decrease = self
+            .decrease
+            .update(deviation + self.config.delta, value, -1.0);
+        let shifted = if increase > self.config.threshold {
+            Some(self.increase)
+        } else if decrease > self.config.threshold {
+            Some(self.decrease)
+        } else {
+            None
+        };
+        match shifted {
+            Some(cusum) => {
+                // The mean already absorbed the samples since the extreme except this one,
+                // which belong to the level after the shift
+                let n_absorbed = (self.n_samples - 1) as f64;
+                let n_shifted = (cusum.n_since_extreme - 1) as f64;
+                let before = if n_absorbed > n_shifted {
+                    (self.mean * n_absorbed - (cusum.sum_since_extreme - value))
+                        / (n_absorbed - n_shifted)
+                } else {
+                    self.mean
+                };
+                let after = cusum.sum_since_extreme / cusum.n_since_extreme as f64;
+                self.restart(after, cusum.n_since_extreme);
+                Some((before, after))
+            }
+            None => {
+                self.mean += (value - self.mean) / self.n_samples as f64;
+                None
+            }
+        }
+    }
+
+    /// Continues from the level after a shift, learned from `n_samples` samples
+    fn restart(&mut self, mean: f64, n_samples: usize) {
+        self.n_samples = n_samples.max(self.config.min_samples);
+        self.mean = mean;
+        self.increase = Cusum::default();
+        self.decrease = Cusum::default();
+    }
+}
+
+#[test]
+fn detects_level_shifts() {
+    let mut detector = PageHinkley::new(ChangeDetection::default());
+    let noise = [1.0, 1.1, 0.9, 1.05, 0.95];
+    let mut shifts = Vec::new();
+    for i in 0..200 {
+        let level = if (50..120).contains(&i) { 0.2 } else { 0.1 };
+        if let Some(shift) = detector.update(level * noise[i % noise.len()]) {
+            shifts.push((i, shift));
+        }
+    }
+    assert_eq!(shifts.len(), 2);
+    let (i, (before, after)) = shifts[0];
+    assert!((50..55).contains(&i));
+    assert!((before - 0.1).abs() < 0.001);
+    assert!((after - 0.2).abs() < 0.02);
+    let (i, (before, after)) = shifts[1];
+    assert!((120..135).contains(&i));
+    assert!((before - 0.2).abs() < 0.002);
+    assert!(after < 0.15);
+}
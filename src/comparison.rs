@@ -0,0 +1,644 @@
+use crate::{durations_mean, durations_std_dev, StatsSnapshot};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Significance level used by [`StatsSnapshot::compare`] by default
+pub const DEFAULT_ALPHA: f64 = 0.05;
+
+/// Welch's t-test for equal means of two samples with possibly unequal variances
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WelchTTest {
+    /// Positive when the second sample has the larger mean
+    pub t: f64,
+    /// Welch–Satterthwaite degrees of freedom
+    pub df: f64,
+    /// Two-sided p-value
+    pub p_value: f64,
+}
+
+/// Mann-Whitney U test whether values of one sample tend to be larger than of the other
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MannWhitneyU {
+    /// Number of pairs in which the value of the second sample is larger, ties counting half
+    pub u: f64,
+    /// Normal approximation of `u` with tie and continuity corrections
+    pub z: f64,
+    /// Two-sided p-value
+    pub p_value: f64,
+}
+
+/// Comparison of round trip times before and after a change, e.g. a release
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comparison {
+    pub n_before: usize,
+    pub n_after: usize,
+    pub mean_before: Duration,
+    pub mean_after: Duration,
+    pub welch: Option<WelchTTest>,
+    pub mann_whitney: Option<MannWhitneyU>,
+    /// Difference of the means in pooled standard deviations
+    pub cohens_d: Option<f64>,
+    /// Probability that a round trip after is longer than one before minus the reverse,
+    /// from -1 to 1
+    pub rank_biserial: Option<f64>,
+    /// Round trips got longer, significantly by both tests
+    pub regression: bool,
+}
+
+impl Comparison {
+    /// Compares the round trip times, or `None` if one of the samples is empty
+    pub fn from_durations(before: &[Duration], after: &[Duration], alpha: f64) -> Option<Self> {
+        let welch = welch_t_test(before, after);
+        let mann_whitney = mann_whitney_u(before, after);
+        let mean_before = durations_mean(before)?;
+        let mean_after = durations_mean(after)?;
+        let is_significant = |p_value: Option<f64>| p_value.is_some_and(|p| p < alpha);
+        let regression = mean_after > mean_before
+            && mann_whitney.is_some_and(|test| test.z > 0.0)
+            && is_significant(welch.map(|test| test.p_value))
+            && is_significant(mann_whitney.map(|test| test.p_value));
+        Some(Self {
+            n_before: before.len(),
+            n_after: after.len(),
+            mean_before,
+            mean_after,
+            welch,
+            mann_whitney,
+            cohens_d: cohens_d(before, after),
+            rank_biserial: mann_whitney
+                .map(|test| 2.0 * test.u / (before.len() * after.len()) as f64 - 1.0),
+            regression,
+        })
+    }
+}
+
+/// Per-peer and overall comparison of the raw round trip times of two snapshots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotComparison {
+    pub alpha: f64,
+    /// Peers with raw round trip times in both snapshots, sorted by their ids
+    pub peers: Vec<(String, Comparison)>,
+    /// Change over all these peers, where every peer weighs the same regardless of
+    /// its number of samples and its usual round trip time
+    pub overall: Option<OverallComparison>,
+}
+
+/// Change of the round trip times of several peers, where every peer weighs the same.
+/// Pooling the samples of all peers instead lets peers with many or long round trips
+/// dominate, so that the pooled change may even point the other way than for most peers.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OverallComparison {
+    pub n_peers: usize,
+    /// Mean over the peers of `mean_after / mean_before - 1`
+    pub mean_relative_change: f64,
+    /// Welch's t-test of the round trip times divided by the mean before of their peer,
+    /// where the samples of a peer weigh `1 / n` and the Kish effective sample sizes
+    /// stand in for the number of samples
+    pub welch: Option<WelchTTest>,
+    /// Mann-Whitney U test stratified by peer, with `u` summed over the peers weighted by
+    /// `1 / (n_before * n_after)`, so that each peer adds its share of pairs in which
+    /// the round trip after is longer
+    pub mann_whitney: Option<MannWhitneyU>,
+    /// Difference of the weighted relative means in pooled standard deviations
+    pub cohens_d: Option<f64>,
+    /// Mean over the peers of their rank-biserial correlations, from -1 to 1
+    pub rank_biserial: Option<f64>,
+    /// Round trips got longer, significantly by both tests
+    pub regression: bool,
+}
+
+impl OverallComparison {
+    /// Compares the round trip times `(before, after)` of each peer,
+    /// or `None` if no peer has samples in both
+    pub fn from_durations(peers: &[(&[Duration], &[Duration])], alpha: f64) -> Option<Self> {
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        let mut changes = Vec::new();
+        let mut rank_biserials = Vec::new();
+        let (mut u, mut mean_u, mut variance_u) = (0.0, 0.0, 0.0);
+        for &(peer_before, peer_after) in peers {
+            let mean_before = match durations_mean(peer_before) {
+                Some(mean) if !mean.is_zero() => mean.as_secs_f64(),
+                _ => continue,
+            };
+            let mean_after = match durations_mean(peer_after) {
+                Some(mean) => mean.as_secs_f64(),
+                None => continue,
+            };
+            changes.push(mean_after / mean_before - 1.0);
+            let relative = |durations: &[Duration], values: &mut Vec<(f64, f64)>| {
+                let weight = 1.0 / durations.len() as f64;
+                values.extend(
+                    durations
+                        .iter()
+                        .map(|duration| (duration.as_secs_f64() / mean_before, weight)),
+                );
+            };
+            relative(peer_before, &mut before);
+            relative(peer_after, &mut after);
+            let (n_before, n_after) = (peer_before.len() as f64, peer_after.len() as f64);
+            if let Some(ranks) = RankSum::new(peer_before, peer_after) {
+                let weight = 1.0 / (n_before * n_after);
+                u += weight * ranks.u;
+                mean_u += weight * ranks.mean_u;
+                variance_u += weight.powi(2) * ranks.variance_u;
+                rank_biserials.push(2.0 * ranks.u / (n_before * n_after) - 1.0);
+            }
+        }
+        if changes.is_empty() {
+            return None;
+        }
+        let mean_relative_change = changes.iter().sum::<f64>() / changes.len() as f64;
+        let (before, after) = (WeightedSample::new(&before)?, WeightedSample::new(&after)?);
+        let welch = welch(&before, &after);
+        let mann_whitney = Some(variance_u)
+            .filter(|&variance| variance > 0.0)
+            .map(|variance| {
+                let z = (u - mean_u) / variance.sqrt();
+                MannWhitneyU {
+                    u,
+                    z,
+                    p_value: normal_two_sided_p(z),
+                }
+            });
+        let is_significant = |p_value: Option<f64>| p_value.is_some_and(|p| p < alpha);
+        let regression = mean_relative_change > 0.0
+            && mann_whitney.is_some_and(|test| test.z > 0.0)
+            && is_significant(welch.map(|test| test.p_value))
+            && is_significant(mann_whitney.map(|test| test.p_value));
+        Some(Self {
+            n_peers: changes.len(),
+            mean_relative_change,
+            welch,
+            mann_whitney,
+            cohens_d: weighted_cohens_d(&before, &after),
+            rank_biserial: Some(rank_biserials.iter().sum::<f64>() / rank_biserials.len() as f64)
+                .filter(|_| !rank_biserials.is_empty()),
+            regression,
+        })
+    }
+}
+
+impl SnapshotComparison {
+    /// Ids of the peers whose round trip times got significantly longer
+    pub fn regressions(&self) -> impl Iterator<Item = &str> {
+        self.peers
+            .iter()
+            .filter(|(_, comparison)| comparison.regression)
+            .map(|(peer_id, _)| peer_id.as_str())
+    }
+}
+
+impl StatsSnapshot {
+    /// Tests whether round trip times in the `after` snapshot are longer than in this one
+    /// at the significance level `alpha`. Both snapshots must be taken with raw samples.
+    /// Each peer is tested separately, so with many peers some regressions are flagged
+    /// by chance and a smaller `alpha` should be used.
+    pub fn compare(&self, after: &StatsSnapshot, alpha: f64) -> SnapshotComparison {
+        let mut peers = Vec::new();
+        let mut samples = Vec::new();
+        for peer in &self.peers {
+            let before = match &peer.pings {
+                Some(pings) => pings,
+                None => continue,
+            };
+            let after = match after
+                .peer(&peer.peer_id)
+                .and_then(|peer| peer.pings.as_ref())
+            {
+                Some(pings) => pings,
+                None => continue,
+            };
+            if let Some(comparison) = Comparison::from_durations(before, after, alpha) {
+                peers.push((peer.peer_id.clone(), comparison));
+                samples.push((&before[..], &after[..]));
+            }
+        }
+        peers.sort_by(|(a, _), (b, _)| a.cmp(b));
+        SnapshotComparison {
+            alpha,
+            peers,
+            overall: OverallComparison::from_durations(&samples, alpha),
+        }
+    }
+}
+
+/// Welch's t-test of the means of `before` and `after`, `None` with less than 2 samples
+/// in either or without any variance
+pub fn welch_t_test(before: &[Duration], after: &[Duration]) -> Option<WelchTTest> {
+    let sample = |durations: &[Duration]| {
+        Some(WeightedSample {
+            mean: durations_mean(durations)?.as_secs_f64(),
+            variance: sample_variance(durations)?,
+            n: durations.len() as f64,
+        })
+    };
+    welch(&sample(before)?, &sample(after)?)
+}
+
+/// Welch's t-test of two samples summarized by their means and unbiased variances
+fn welch(before: &WeightedSample, after: &WeightedSample) -> Option<WelchTTest> {
+    if before.n < 2.0 || after.n < 2.0 {
+        return None;
+    }
+    let error_before = before.variance / before.n;
+    let error_after = after.variance / after.n;
+    let standard_error = (error_before + error_after).sqrt();
+    if standard_error == 0.0 {
+        return None;
+    }
+    let t = (after.mean - before.mean) / standard_error;
+    let df = (error_before + error_after).powi(2)
+        / (error_before.powi(2) / (before.n - 1.0) + error_after.powi(2) / (after.n - 1.0));
+    Some(WelchTTest {
+        t,
+        df,
+        p_value: student_t_two_sided_p(t, df),
+    })
+}
+
+/// Mean and unbiased variance of weighted values, with the Kish effective sample size
+/// `(Σw)² / Σw²` as the number of samples
+struct WeightedSample {
+    mean: f64,
+    variance: f64,
+    n: f64,
+}
+
+impl WeightedSample {
+    /// Summarizes `(value, weight)` pairs, `None` without any weight
+    fn new(values: &[(f64, f64)]) -> Option<Self> {
+        let (sum, sum_of_squares) = values.iter().fold((0.0, 0.0), |(s, sq), (_, weight)| {
+            (s + weight, sq + weight * weight)
+        });
+        if sum <= 0.0 {
+            return None;
+        }
+        let mean = values
+            .iter()
+            .map(|(value, weight)| value * weight)
+            .sum::<f64>()
+            / sum;
+        let n = sum * sum / sum_of_squares;
+        let variance = values
+            .iter()
+            .map(|(value, weight)| weight * (value - mean).powi(2))
+            .sum::<f64>()
+            / sum
+            * n
+            / (n - 1.0);
+        Some(Self {
+            mean,
+            variance: if n > 1.0 { variance } else { 0.0 },
+            n,
+        })
+    }
+}
+
+/// Mann-Whitney U test of `after` against `before`, `None` if either is empty
+/// or all values are equal
+pub fn mann_whitney_u(before: &[Duration], after: &[Duration]) -> Option<MannWhitneyU> {
+    let ranks = RankSum::new(before, after)?;
+    let difference = ranks.u - ranks.mean_u;
+    let corrected = (difference.abs() - 0.5).max(0.0) * difference.signum();
+    let z = corrected / ranks.variance_u.sqrt();
+    Some(MannWhitneyU {
+        u: ranks.u,
+        z,
+        p_value: normal_two_sided_p(z),
+    })
+}
+
+/// Mann-Whitney `u` of `after` against `before` with its mean and tie-corrected variance
+/// under the null hypothesis
+struct RankSum {
+    u: f64,
+    mean_u: f64,
+    variance_u: f64,
+}
+
+impl RankSum {
+    /// `None` if either sample is empty or all values are equal
+    fn new(before: &[Duration], after: &[Duration]) -> Option<Self> {
+        if before.is_empty() || after.is_empty() {
+            return None;
+        }
+        let mut values: Vec<(Duration, bool)> = before
+            .iter()
+            .map(|&value| (value, false))
+            .chain(after.iter().map(|&value| (value, true)))
+            .collect();
+        values.sort_by_key(|(value, _)| *value);
+
+        // Ties get the mean of their ranks
+        let mut rank_sum_after = 0.0;
+        let mut ties_correction = 0.0;
+        let mut start = 0;
+        while start < values.len() {
+            let end = start
+                + values[start..]
+                    .iter()
+                    .take_while(|(value, _)| *value == values[start].0)
+                    .count();
+            let mean_rank = (start + end + 1) as f64 / 2.0;
+            let n_after_tied = values[start..end]
+                .iter()
+                .filter(|(_, is_after)| *is_after)
+                .count();
+            rank_sum_after += mean_rank * n_after_tied as f64;
+            let n_tied = (end - start) as f64;
+            ties_correction += n_tied.powi(3) - n_tied;
+            start = end;
+        }
+
+        let (n_before, n_after) = (before.len() as f64, after.len() as f64);
+        let n = n_before + n_after;
+        let variance_u =
+            n_before * n_after / 12.0 * ((n + 1.0) - ties_correction / (n * (n - 1.0)));
+        if variance_u <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            u: rank_sum_after - n_after * (n_after + 1.0) / 2.0,
+            mean_u: n_before * n_after / 2.0,
+            variance_u,
+        })
+    }
+}
+
+/// Difference of the means of `after` and `before` divided by the pooled standard deviation
+fn cohens_d(before: &[Duration], after: &[Duration]) -> Option<f64> {
+    let (n_before, n_after) = (before.len() as f64, after.len() as f64);
+    if n_before + n_after < 3.0 {
+        return None;
+    }
+    let pooled_variance = ((n_before - 1.0) * sample_variance(before).unwrap_or(0.0)
+        + (n_after - 1.0) * sample_variance(after).unwrap_or(0.0))
+        / (n_before + n_after - 2.0);
+    if pooled_variance == 0.0 {
+        return None;
+    }
+    let difference = durations_mean(after)?.as_secs_f64() - durations_mean(before)?.as_secs_f64();
+    Some(difference / pooled_variance.sqrt())
+}
+
+/// Difference of the weighted means divided by the pooled standard deviation
+fn weighted_cohens_d(before: &WeightedSample, after: &WeightedSample) -> Option<f64> {
+    if before.n + after.n <= 2.0 {
+        return None;
+    }
+    let pooled_variance = ((before.n - 1.0).max(0.0) * before.variance
+        + (after.n - 1.0).max(0.0) * after.variance)
+        / (before.n + after.n - 2.0);
+    if pooled_variance <= 0.0 {
+        return None;
+    }
+    Some((after.mean - before.mean) / pooled_variance.sqrt())
+}
+
+/// Unbiased variance in seconds squared, from the population standard deviation
+fn sample_variance(durations: &[Duration]) -> Option<f64> {
+    let n = durations.len() as f64;
+    if n < 2.0 {
+        return None;
+    }
+    Some(durations_std_dev(durations)?.as_secs_f64().powi(2) * n / (n - 1.0))
+}
+
+/// Probability of a Student's t-distributed value with `df` degrees of freedom
+/// being at least `|t|` away from 0
+fn student_t_two_sided_p(t: f64, df: f64) -> f64 {
+    if !t.is_finite() {
+        return 0.0;
+    }
+    regularized_incomplete_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+/// Probability of a standard normal value being at least `|z|` away from 0
+fn normal_two_sided_p(z: f64) -> f64 {
+    erfc(z.abs() / std::f64::consts::SQRT_2).min(1.0)
+}
+
+/// Complementary error function with a relative error below 1.2e-7
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let coefficients = [
+        -1.265_512_23,
+        1.000_023_68,
+        0.374_091_96,
+        0.096_784_18,
+        -0.186_288_06,
+        0.278_868_07,
+        -1.135_203_98,
+        1.488_515_87,
+        -0.822_152_23,
+        0.170_872_77,
+    ];
+    let polynomial = coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |acc, coefficient| acc * t + coefficient);
+    let result = t * (-z * z + polynomial).exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+/// Regularized incomplete beta function I_x(a, b)
+fn regularized_incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly only below the mean of the distribution
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Continued fraction of the incomplete beta function evaluated with the modified Lentz method
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 300;
+    const EPSILON: f64 = 1e-14;
+    const TINY: f64 = 1e-300;
+    let guard = |value: f64| if value.abs() < TINY { TINY } else { value };
+
+    let mut c = 1.0;
+    let mut d = 1.0 / guard(1.0 - (a + b) * x / (a + 1.0));
+    let mut result = d;
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / guard(1.0 + even * d);
+        c = guard(1.0 + even / c);
+        result *= d * c;
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / guard(1.0 + odd * d);
+        c = guard(1.0 + odd / c);
+        let step = d * c;
+        result *= step;
+        if (step - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    result
+}
+
+/// Natural logarithm of the gamma function for positive `x` by the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |acc, (i, coefficient)| {
+            acc + coefficient / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+#[test]
+fn correct_p_values() {
+    let epsilon = 1e-3;
+    assert!((normal_two_sided_p(1.96) - 0.05).abs() < epsilon);
+    assert!((normal_two_sided_p(-2.576) - 0.01).abs() < epsilon);
+    assert!((normal_two_sided_p(0.0) - 1.0).abs() < epsilon);
+    assert!((student_t_two_sided_p(2.228, 10.0) - 0.05).abs() < epsilon);
+    assert!((student_t_two_sided_p(-2.086, 20.0) - 0.05).abs() < epsilon);
+    assert!((student_t_two_sided_p(63.657, 1.0) - 0.01).abs() < epsilon);
+    assert!((student_t_two_sided_p(2.750, 30.5) - 0.01).abs() < epsilon);
+    assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-10);
+}
+
+#[test]
+fn correct_mann_whitney_u() {
+    let millis = |values: &[u64]| -> Vec<Duration> {
+        values.iter().map(|&ms| Duration::from_millis(ms)).collect()
+    };
+    let before = millis(&[1, 2, 3, 4]);
+    let after = millis(&[3, 5, 6, 7, 8]);
+    let test = mann_whitney_u(&before, &after).unwrap();
+    // Ranks of after: 3.5, 6, 7, 8, 9
+    assert_eq!(test.u, 33.5 - 15.0);
+    assert!(test.z > 0.0);
+    assert!(test.p_value < 0.05);
+    assert_eq!(mann_whitney_u(&before, &before).unwrap().p_value, 1.0);
+    assert!(mann_whitney_u(&before, &[]).is_none());
+}
+
+#[test]
+fn correct_welch_t_test() {
+    let millis = |values: &[u64]| -> Vec<Duration> {
+        values.iter().map(|&ms| Duration::from_millis(ms)).collect()
+    };
+    let test = welch_t_test(&millis(&[10, 12, 14]), &millis(&[20, 24, 28, 32])).unwrap();
+    // Squared standard errors 4/3 and 20/3 ms squared
+    assert!((test.t - 14.0 / 8f64.sqrt()).abs() < 1e-6);
+    let df = 64.0 / ((4f64 / 3.0).powi(2) / 2.0 + (20f64 / 3.0).powi(2) / 3.0);
+    assert!((test.df - df).abs() < 1e-6);
+    assert!(test.p_value > 0.001 && test.p_value < 0.05);
+    assert!(welch_t_test(&millis(&[10]), &millis(&[20, 30])).is_none());
+    assert!(welch_t_test(&millis(&[10, 10]), &millis(&[10, 10])).is_none());
+}
+
+#[test]
+fn flags_significant_regressions() {
+    let stats_before = crate::Stats::new(100, "1".to_string());
+    let stats_after = crate::Stats::new(100, "1".to_string());
+    for i in 0..30 {
+        let noise = Duration::from_micros(i * 37 % 11 * 100);
+        stats_before.add_ping("2".to_string(), Duration::from_millis(20) + noise);
+        stats_after.add_ping("2".to_string(), Duration::from_millis(25) + noise);
+        stats_before.add_ping("3".to_string(), Duration::from_millis(40) + noise);
+        stats_after.add_ping("3".to_string(), Duration::from_millis(40) + noise * 2 / 3);
+    }
+    stats_after.add_ping("4".to_string(), Duration::from_millis(10));
+
+    let comparison = stats_before
+        .snapshot(true)
+        .compare(&stats_after.snapshot(true), DEFAULT_ALPHA);
+    assert_eq!(comparison.peers.len(), 2);
+    assert_eq!(comparison.regressions().collect::<Vec<_>>(), vec!["2"]);
+    let (_, slower) = &comparison.peers[0];
+    assert!(slower.welch.unwrap().p_value < 1e-6);
+    assert!(slower.cohens_d.unwrap() > 1.0);
+    assert_eq!(slower.rank_biserial, Some(1.0));
+    let (_, unchanged) = &comparison.peers[1];
+    assert!(!unchanged.regression);
+    assert!(unchanged.mann_whitney.unwrap().p_value > DEFAULT_ALPHA);
+    let overall = comparison.overall.unwrap();
+    assert_eq!(overall.n_peers, 2);
+    assert!((overall.mean_relative_change - 0.12).abs() < 0.01);
+    // Half of the peers got a quarter slower
+    assert!(overall.regression);
+    assert!(stats_before
+        .snapshot(false)
+        .compare(&stats_after.snapshot(false), DEFAULT_ALPHA)
+        .peers
+        .is_empty());
+}
+
+#[test]
+fn overall_comparison_weighs_peers_equally() {
+    let noisy = |millis: u64, n: u64| -> Vec<Duration> {
+        (0..n)
+            .map(|i| Duration::from_micros(millis * 1000 + i * 37 % 11 * millis * 10))
+            .collect()
+    };
+    // Pooling would report the single busy and slow peer getting faster
+    let peers = [
+        (noisy(1000, 1000), noisy(900, 1000)),
+        (noisy(10, 20), noisy(12, 20)),
+        (noisy(20, 20), noisy(23, 20)),
+        (noisy(30, 20), noisy(37, 20)),
+        (noisy(40, 20), noisy(47, 20)),
+    ];
+    let samples: Vec<(&[Duration], &[Duration])> = peers
+        .iter()
+        .map(|(before, after)| (&before[..], &after[..]))
+        .collect();
+    let pooled_before: Vec<Duration> = peers
+        .iter()
+        .flat_map(|(before, _)| before.clone())
+        .collect();
+    let pooled_after: Vec<Duration> = peers.iter().flat_map(|(_, after)| after.clone()).collect();
+    let pooled = Comparison::from_durations(&pooled_before, &pooled_after, DEFAULT_ALPHA).unwrap();
+    assert!(pooled.mean_after < pooled.mean_before);
+
+    let overall = OverallComparison::from_durations(&samples, DEFAULT_ALPHA).unwrap();
+    assert_eq!(overall.n_peers, 5);
+    assert!(
+        (overall.mean_relative_change - (-0.1 + 0.2 + 0.15 + 0.7 / 3.0 + 0.175) / 5.0).abs() < 1e-9
+    );
+    assert!(overall.welch.unwrap().t > 0.0);
+    assert!(overall.mann_whitney.unwrap().z > 0.0);
+    assert!(overall.cohens_d.unwrap() > 0.0);
+    assert!((overall.rank_biserial.unwrap() - 0.6).abs() < 0.01);
+    assert!(overall.regression);
+    assert!(OverallComparison::from_durations(&[], DEFAULT_ALPHA).is_none());
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+/// Two-sided critical values of Student's t-distribution at 90%, 95% and 99% confidence
+/// for 1 to 30 degrees of freedom
+const T_TABLE: [[f64; 3]; 30] = [
+    [6.314, 12.706, 63.657],
+    [2.920, 4.303, 9.925],
+    [2.353, 3.182, 5.841],
+    [2.132, 2.776, 4.604],
+    [2.015, 2.571, 4.032],
+    [1.943, 2.447, 3.707],
+    [1.895, 2.365, 3.499],
+    [1.860, 2.306, 3.355],
+    [1.833, 2.262, 3.250],
+    [1.812, 2.228, 3.169],
+    [1.796, 2.201, 3.106],
+    [1.782, 2.179, 3.055],
+    [1.771, 2.160, 3.012],
+    [1.761, 2.145, 2.977],
+    [1.753, 2.131, 2.947],
+    [1.746, 2.120, 2.921],
+    [1.740, 2.110, 2.898],
+    [1.734, 2.101, 2.878],
+    [1.729, 2.093, 2.861],
+    [1.725, 2.086, 2.845],
+    [1.721, 2.080, 2.831],
+    [1.717, 2.074, 2.819],
+    [1.714, 2.069, 2.807],
+    [1.711, 2.064, 2.797],
+    [1.708, 2.060, 2.787],
+    [1.706, 2.056, 2.779],
+    [1.703, 2.052, 2.771],
+    [1.701, 2.048, 2.763],
+    [1.699, 2.045, 2.756],
+    [1.697, 2.042, 2.750],
+];
+
+/// Critical values for larger degrees of freedom, each used up to the next tabulated one
+const T_TABLE_TAIL: [(usize, [f64; 3]); 3] = [
+    (40, [1.684, 2.021, 2.704]),
+    (60, [1.671, 2.000, 2.660]),
+    (120, [1.658, 1.980, 2.617]),
+];
+
+/// Confidence level of the intervals reported by [`crate::Stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConfidenceLevel {
+    P90,
+    #[default]
+    P95,
+    P99,
+}
+
+impl ConfidenceLevel {
+    fn index(self) -> usize {
+        match self {
+            ConfidenceLevel::P90 => 0,
+            ConfidenceLevel::P95 => 1,
+            ConfidenceLevel::P99 => 2,
+        }
+    }
+
+    /// Confidence as a fraction, e.g. `0.95`
+    pub fn fraction(self) -> f64 {
+        [0.90, 0.95, 0.99][self.index()]
+    }
+
+    /// Two-sided critical value of the standard normal distribution
+    pub fn z(self) -> f64 {
+        [1.645, 1.960, 2.576][self.index()]
+    }
+
+    /// Two-sided critical value of Student's t-distribution with `df` degrees of freedom.
+    /// Between tabulated values the smaller degrees of freedom are used, which widens the interval.
+    pub fn t(self, df: usize) -> Option<f64> {
+        match df {
+            0 => None,
+            1..=30 => Some(T_TABLE[df - 1][self.index()]),
+            31..=1000 => Some(
+                T_TABLE_TAIL
+                    .iter()
+                    .rev()
+                    .find(|(tail_df, _)| *tail_df <= df)
+                    .map_or(T_TABLE[29][self.index()], |(_, values)| {
+                        values[self.index()]
+                    }),
+            ),
+            _ => Some(self.z()),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfidenceLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = match self {
+            ConfidenceLevel::P90 => "90%",
+            ConfidenceLevel::P95 => "95%",
+            ConfidenceLevel::P99 => "99%",
+        };
+        f.write_str(percent)
+    }
+}
+
+#[test]
+fn correct_t_critical_values() {
+    assert_eq!(ConfidenceLevel::P95.t(0), None);
+    assert_eq!(ConfidenceLevel::P95.t(1), Some(12.706));
+    assert_eq!(ConfidenceLevel::P99.t(30), Some(2.750));
+    assert_eq!(ConfidenceLevel::P90.t(50), Some(1.684));
+    assert_eq!(ConfidenceLevel::P95.t(500), Some(1.980));
+    assert_eq!(ConfidenceLevel::P95.t(5000), Some(1.960));
+}
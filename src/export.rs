@@ -0,0 +1,382 @@
+use crate::{
+    format::{csv_field, format_rfc3339},
+    write_atomic, PeerIdentifier, Sample, Stats, StatsSnapshot, Window,
+};
+use chashmap::CHashMap;
+use std::{
+    fmt::{self, Write as _},
+    io::{self, Write as _},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime},
+};
+
+const SUMMARY_CSV_HEADER: &str = "peer,\
+ping_mean_secs,ping_std_dev_secs,ping_error_secs,ping_n_samples,ping_n_outliers,\
+ping_min_secs,ping_max_secs,ping_median_secs,ping_mad_secs,ping_jitter_secs,\
+rate_mean_bytes_per_sec,rate_std_dev_bytes_per_sec,rate_error_bytes_per_sec,\
+transmission_n_samples,transmission_n_outliers,transmission_n_bytes,\
+rate_min_bytes_per_sec,rate_max_bytes_per_sec,rate_median_bytes_per_sec,rate_mad_bytes_per_sec,\
+loss_rate,loss_ci_low,loss_ci_high,ping_n_sent,confidence";
+
+const SAMPLES_CSV_HEADER: &str = "peer,metric,value,timestamp";
+
+/// Format of a file written by [`Stats::save_as`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// Human readable report, as written by [`Stats::save_to_file`]
+    #[default]
+    Text,
+    /// [`StatsSnapshot`] without raw samples
+    Json,
+    /// One row of summaries per peer
+    SummaryCsv,
+    /// One row per raw sample, with the peer, the metric, the value and the wall-clock time
+    SamplesCsv,
+    /// Raw samples encoded by [`Stats::encode_binary`]
+    Binary,
+    /// Prometheus text exposition format
+    Prometheus,
+}
+
+impl ReportFormat {
+    pub const ALL: [ReportFormat; 6] = [
+        ReportFormat::Text,
+        ReportFormat::Json,
+        ReportFormat::SummaryCsv,
+        ReportFormat::SamplesCsv,
+        ReportFormat::Binary,
+        ReportFormat::Prometheus,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ReportFormat::Text => "text",
+            ReportFormat::Json => "json",
+            ReportFormat::SummaryCsv => "csv",
+            ReportFormat::SamplesCsv => "samples-csv",
+            ReportFormat::Binary => "binary",
+            ReportFormat::Prometheus => "prometheus",
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name() == name)
+            .ok_or_else(|| format!("Unknown report format {:?}", name))
+    }
+}
+
+impl<P: PeerIdentifier> Stats<P> {
+    /// Replaces the file with the stats in `format` atomically
+    pub fn save_as(&self, filename: &str, format: ReportFormat) -> io::Result<()> {
+        let report = match format {
+            ReportFormat::Text => self.to_string().into_bytes(),
+            ReportFormat::Json => self.snapshot(false).to_json()?.into_bytes(),
+            ReportFormat::SummaryCsv => self.to_summary_csv().into_bytes(),
+            ReportFormat::SamplesCsv => self.to_samples_csv().into_bytes(),
+            ReportFormat::Binary => self.encode_binary(),
+            ReportFormat::Prometheus => self.to_prometheus().into_bytes(),
+        };
+        write_atomic(filename, |file| file.write_all(&report))
+    }
+
+    /// One row of ping, transmission and loss summaries per peer, see [`StatsSnapshot::to_summary_csv`]
+    pub fn to_summary_csv(&self) -> String {
+        self.snapshot(false).to_summary_csv()
+    }
+
+    /// One row per sample in the windows with the columns `peer`, `metric`, `value` and
+    /// `timestamp` in RFC 3339. Metrics are `rtt` in seconds, `ping_answered` as 1 or 0
+    /// and `transmission_rate` in bytes per second.
+    pub fn to_samples_csv(&self) -> String {
+        self.prune();
+        let now = Instant::now();
+        let mut peers: Vec<P> = self
+            .pings_to_peers
+            .clone()
+            .into_iter()
+            .map(|(peer, _)| peer)
+            .chain(self.ping_outcomes.clone().into_iter().map(|(peer, _)| peer))
+            .chain(
+                self.transmissions_rates
+                    .clone()
+                    .into_iter()
+                    .map(|(peer, _)| peer),
+            )
+            .collect();
+        peers.sort_by_key(|peer| peer.to_string());
+        peers.dedup();
+        let mut csv = format!("{}\n", SAMPLES_CSV_HEADER);
+        for peer in peers {
+            let peer_id = csv_field(&peer.to_string());
+            for sample in window_samples(&self.pings_to_peers, &peer, now) {
+                write_sample_row(
+                    &mut csv,
+                    &peer_id,
+                    "rtt",
+                    sample.value,
+                    Some(sample.taken_at),
+                );
+            }
+            for sample in window_samples(&self.ping_outcomes, &peer, now) {
+                let answered = u8::from(sample.value);
+                write_sample_row(
+                    &mut csv,
+                    &peer_id,
+                    "ping_answered",
+                    answered,
+                    Some(sample.taken_at),
+                );
+            }
+            for sample in window_samples(&self.transmissions_rates, &peer, now) {
+                let rate = sample.value.rate();
+                write_sample_row(
+                    &mut csv,
+                    &peer_id,
+                    "transmission_rate",
+                    rate,
+                    Some(sample.taken_at),
+                );
+            }
+        }
+        csv
+    }
+}
+
+fn window_samples<P: PeerIdentifier, T: Clone>(
+    map: &CHashMap<P, Window<T>>,
+    peer_id: &P,
+    now: Instant,
+) -> Vec<Sample<T>> {
+    match map.get_mut(peer_id) {
+        Some(mut window) => {
+            window.expire(now);
+            window.samples().cloned().collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+impl StatsSnapshot {
+    /// Replaces the file with the snapshot in `format` atomically. Snapshots do not keep
+    /// the windows, so they can not be written as [`ReportFormat::Text`] or
+    /// [`ReportFormat::Prometheus`].
+    pub fn save_as(&self, filename: &str, format: ReportFormat) -> io::Result<()> {
+        let report = match format {
+            ReportFormat::Json => self.to_json()?.into_bytes(),
+            ReportFormat::SummaryCsv => self.to_summary_csv().into_bytes(),
+            ReportFormat::SamplesCsv => self.to_samples_csv().into_bytes(),
+            ReportFormat::Binary => self.encode_binary(),
+            ReportFormat::Text | ReportFormat::Prometheus => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Snapshots can not be saved as {}", format),
+                ))
+            }
+        };
+        write_atomic(filename, |file| file.write_all(&report))
+    }
+
+    /// One row of ping, transmission and loss summaries per peer.
+    /// Times are in seconds, rates in bytes per second, and unknown values are left empty.
+    pub fn to_summary_csv(&self) -> String {
+        let mut csv = format!("{}\n", SUMMARY_CSV_HEADER);
+        for peer in &self.peers {
+            let mut row = vec![csv_field(&peer.peer_id)];
+            match &peer.ping {
+                Some(ping) => row.extend(vec![
+                    secs(ping.mean),
+                    secs(ping.std_dev),
+                    ping.error.map(secs).unwrap_or_default(),
+                    ping.n_samples.to_string(),
+                    ping.n_outliers.to_string(),
+                    secs(ping.min),
+                    secs(ping.max),
+                    secs(ping.median),
+                    secs(ping.mad),
+                    ping.jitter.map(secs).unwrap_or_default(),
+                ]),
+                None => row.extend(vec![String::new(); 10]),
+            }
+            match &peer.transmission {
+                Some(transmission) => row.extend(vec![
+                    transmission.mean.to_string(),
+                    transmission.std_dev.to_string(),
+                    transmission
+                        .error
+                        .map(|error| error.to_string())
+                        .unwrap_or_default(),
+                    transmission.n_samples.to_string(),
+                    transmission.n_outliers.to_string(),
+                    transmission.n_bytes.to_string(),
+                    transmission.min.to_string(),
+                    transmission.max.to_string(),
+                    transmission.median.to_string(),
+                    transmission.mad.to_string(),
+                ]),
+                None => row.extend(vec![String::new(); 10]),
+            }
+            match &peer.loss {
+                Some(loss) => row.extend(vec![
+                    loss.rate.to_string(),
+                    loss.ci_low.to_string(),
+                    loss.ci_high.to_string(),
+                    loss.n_sent.to_string(),
+                ]),
+                None => row.extend(vec![String::new(); 4]),
+            }
+            let confidence = peer
+                .ping
+                .as_ref()
+                .map(|ping| ping.confidence)
+                .or_else(|| peer.transmission.as_ref().map(|t| t.confidence))
+                .or_else(|| peer.loss.as_ref().map(|loss| loss.confidence));
+            row.push(confidence.map(|c| c.to_string()).unwrap_or_default());
+            let _ = writeln!(csv, "{}", row.join(","));
+        }
+        csv
+    }
+
+    /// Raw samples of a snapshot taken with them in the layout of [`Stats::to_samples_csv`].
+    /// Snapshots do not keep the time of each sample, so the `timestamp` column is empty.
+    pub fn to_samples_csv(&self) -> String {
+        let mut csv = format!("{}\n", SAMPLES_CSV_HEADER);
+        for peer in &self.peers {
+            let peer_id = csv_field(&peer.peer_id);
+            for &rtt in peer.pings.iter().flatten() {
+                write_sample_row(&mut csv, &peer_id, "rtt", rtt, None);
+            }
+            for transmission in peer.transmissions.iter().flatten() {
+                write_sample_row(
+                    &mut csv,
+                    &peer_id,
+                    "transmission_rate",
+                    transmission.rate(),
+                    None,
+                );
+            }
+        }
+        csv
+    }
+}
+
+/// Value of a raw sample as written to CSV
+trait CsvValue {
+    fn to_csv_value(&self) -> String;
+}
+
+impl CsvValue for Duration {
+    fn to_csv_value(&self) -> String {
+        secs(*self)
+    }
+}
+
+impl CsvValue for u8 {
+    fn to_csv_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CsvValue for f64 {
+    fn to_csv_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+fn write_sample_row(
+    csv: &mut String,
+    peer_id: &str,
+    metric: &str,
+    value: impl CsvValue,
+    taken_at: Option<SystemTime>,
+) {
+    let _ = writeln!(
+        csv,
+        "{},{},{},{}",
+        peer_id,
+        metric,
+        value.to_csv_value(),
+        taken_at.map(format_rfc3339).unwrap_or_default()
+    );
+}
+
+fn secs(duration: Duration) -> String {
+    duration.as_secs_f64().to_string()
+}
+
+#[test]
+fn correct_summary_csv() {
+    let stats = Stats::new(100, "1".to_string());
+    stats.add_ping("2".to_string(), Duration::from_millis(10));
+    stats.add_ping("2".to_string(), Duration::from_millis(30));
+    stats.add_ping_timeout("2".to_string());
+    stats.add_transmission("a,b".to_string(), Duration::from_secs(1), 1000);
+
+    let csv = stats.to_summary_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    let n_columns = SUMMARY_CSV_HEADER.split(',').count();
+    assert_eq!(n_columns, 26);
+    assert!(lines[1].starts_with("2,0.02,0.01,"));
+    assert!(lines[1].ends_with(",3,95%"));
+    assert!(lines[2].starts_with("\"a,b\",,,,,,,,,,,1000,0,,1,0,1000,1000,1000,1000,0,"));
+    assert_eq!(lines[2].matches(',').count(), n_columns);
+}
+
+#[test]
+fn correct_samples_csv() {
+    let stats = Stats::new(100, "1".to_string());
+    stats.add_ping("2".to_string(), Duration::from_millis(10));
+    stats.add_ping_timeout("2".to_string());
+    stats.add_transmission("3".to_string(), Duration::from_millis(500), 1000);
+
+    let csv = stats.to_samples_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], SAMPLES_CSV_HEADER);
+    assert_eq!(lines.len(), 5);
+    assert!(lines[1].starts_with("2,rtt,0.01,20"));
+    assert!(lines[1].ends_with('Z'));
+    assert!(lines[2].starts_with("2,ping_answered,1,"));
+    assert!(lines[3].starts_with("2,ping_answered,0,"));
+    assert!(lines[4].starts_with("3,transmission_rate,2000,"));
+
+    let snapshot_csv = stats.snapshot(true).to_samples_csv();
+    assert_eq!(snapshot_csv.lines().nth(1), Some("2,rtt,0.01,"));
+}
+
+#[test]
+fn correct_report_format_names() {
+    for format in ReportFormat::ALL {
+        assert_eq!(format.to_string().parse(), Ok(format));
+    }
+    assert!("xml".parse::<ReportFormat>().is_err());
+}
+
+#[test]
+fn snapshot_saves_as_formats_without_windows() {
+    let path = std::env::temp_dir().join(format!("p2p_node_stats_export_{}", std::process::id()));
+    let filename = path.to_str().unwrap();
+    let stats = Stats::new(100, "1".to_string());
+    stats.add_ping("2".to_string(), Duration::from_millis(10));
+    let snapshot = stats.snapshot(true);
+    snapshot
+        .save_as(filename, ReportFormat::SummaryCsv)
+        .unwrap();
+    let csv = std::fs::read_to_string(filename).unwrap();
+    std::fs::remove_file(filename).unwrap();
+    assert_eq!(csv, snapshot.to_summary_csv());
+    let error = snapshot.save_as(filename, ReportFormat::Text).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
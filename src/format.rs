@@ -0,0 +1,107 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Quotes a CSV field if it contains separators, quotes or line breaks
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Formats `time` in UTC like `20240131T235959.123Z`
+pub(crate) fn format_timestamp(time: SystemTime) -> String {
+    let utc = UtcTime::from(time);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}.{:03}Z",
+        utc.year,
+        utc.month,
+        utc.day,
+        utc.hour,
+        utc.minute,
+        utc.second,
+        utc.nanos / 1_000_000
+    )
+}
+
+/// Formats `time` in UTC as RFC 3339 with microseconds like `2024-01-31T23:59:59.123456Z`
+pub(crate) fn format_rfc3339(time: SystemTime) -> String {
+    let utc = UtcTime::from(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        utc.year,
+        utc.month,
+        utc.day,
+        utc.hour,
+        utc.minute,
+        utc.second,
+        utc.nanos / 1_000
+    )
+}
+
+/// Calendar date and time of day in UTC, times before the epoch are clamped to it
+struct UtcTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u64,
+    minute: u64,
+    second: u64,
+    nanos: u32,
+}
+
+impl From<SystemTime> for UtcTime {
+    fn from(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_epoch.as_secs();
+        let (year, month, day) = civil_from_days((secs / 86400) as i64);
+        let secs_of_day = secs % 86400;
+        Self {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day / 60 % 60,
+            second: secs_of_day % 60,
+            nanos: since_epoch.subsec_nanos(),
+        }
+    }
+}
+
+/// Whether `suffix` looks like a timestamp formatted by [`format_timestamp`]
+pub(crate) fn is_timestamp(suffix: &str) -> bool {
+    suffix.len() == "20240131T235959.123Z".len()
+        && suffix.ends_with('Z')
+        && suffix.as_bytes()[8] == b'T'
+        && suffix.as_bytes()[15] == b'.'
+}
+
+/// Year, month and day of the date `days` after 1970-01-01 in the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[test]
+fn correct_format_timestamp() {
+    use std::time::Duration;
+
+    assert_eq!(format_timestamp(UNIX_EPOCH), "19700101T000000.000Z");
+    let time = UNIX_EPOCH + Duration::from_millis(1_709_251_199_123);
+    assert_eq!(format_timestamp(time), "20240229T235959.123Z");
+    assert!(is_timestamp(&format_timestamp(time)));
+    assert_eq!(format_rfc3339(time), "2024-02-29T23:59:59.123000Z");
+}
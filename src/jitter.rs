@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Interarrival jitter estimator as specified in RFC 3550 section 6.4.1,
+/// applied to consecutive round trip times
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct JitterEstimator {
+    last_rtt: Duration,
+    jitter: Duration,
+}
+
+impl JitterEstimator {
+    pub fn new(rtt: Duration) -> Self {
+        Self {
+            last_rtt: rtt,
+            jitter: Duration::from_secs(0),
+        }
+    }
+
+    /// Moves the jitter by 1/16 of the difference from the variation to the previous round trip time
+    pub fn update(&mut self, rtt: Duration) {
+        let difference = self.last_rtt.abs_diff(rtt).as_secs_f64();
+        let jitter = self.jitter.as_secs_f64();
+        self.jitter = Duration::from_secs_f64(jitter + (difference - jitter) / 16.0);
+        self.last_rtt = rtt;
+    }
+
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+}
+
+#[test]
+fn correct_jitter_estimator() {
+    let mut estimator = JitterEstimator::new(Duration::from_millis(100));
+    assert_eq!(estimator.jitter(), Duration::from_secs(0));
+    estimator.update(Duration::from_millis(260));
+    assert_eq!(estimator.jitter(), Duration::from_millis(10));
+    estimator.update(Duration::from_millis(260));
+    assert_eq!(estimator.jitter(), Duration::from_micros(9375));
+}
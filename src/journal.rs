@@ -0,0 +1,110 @@
+use crate::{atomic::sync_dir, PeerIdentifier, Stats, StatsSnapshot};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, prelude::*, SeekFrom},
+    path::Path,
+};
+
+impl<P: PeerIdentifier> Stats<P> {
+    /// Appends a snapshot as one JSON line to the journal at `filename` instead of overwriting it.
+    /// When the journal would grow beyond `max_bytes`, it is first moved to `<filename>.1`,
+    /// replacing the previous one. A record torn by a crash during the last append is dropped.
+    pub fn append_to_journal(&self, filename: &str, max_bytes: u64) -> io::Result<()> {
+        let mut record = serde_json::to_vec(&self.snapshot(false))?;
+        record.push(b'\n');
+        let path = Path::new(filename);
+        let len = match fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => 0,
+            Err(error) => return Err(error),
+        };
+        let rotate = len > 0 && len + record.len() as u64 > max_bytes;
+        if rotate {
+            fs::rename(path, format!("{}.1", filename))?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        truncate_torn_record(&mut file)?;
+        file.write_all(&record)?;
+        file.sync_data()?;
+        // Persist the rename and the entry of a new journal together with its first record
+        if rotate || len == 0 {
+            sync_dir(path);
+        }
+        Ok(())
+    }
+}
+
+impl StatsSnapshot {
+    /// Reads the snapshots appended by [`Stats::append_to_journal`] from the oldest to the newest.
+    /// A torn final record is skipped, other malformed records are an error.
+    pub fn read_journal(filename: &str) -> io::Result<Vec<StatsSnapshot>> {
+        let journal = fs::read(filename)?;
+        let mut records: Vec<&[u8]> = journal.split(|&byte| byte == b'\n').collect();
+        // The part after the last newline is empty unless the final record is torn
+        records.pop();
+        records
+            .into_iter()
+            .map(|record| serde_json::from_slice(record).map_err(io::Error::from))
+            .collect()
+    }
+}
+
+/// Cuts the file after its last newline, dropping a partially appended record
+fn truncate_torn_record(file: &mut File) -> io::Result<()> {
+    const CHUNK_SIZE: u64 = 4096;
+    let len = file.metadata()?.len();
+    let mut end = len;
+    let mut chunk = Vec::new();
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK_SIZE);
+        chunk.resize((end - start) as usize, 0);
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        if let Some(i) = chunk.iter().rposition(|&byte| byte == b'\n') {
+            end = start + i as u64 + 1;
+            break;
+        }
+        end = start;
+    }
+    if end < len {
+        file.set_len(end)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn journal_skips_torn_record() {
+    let dir = std::env::temp_dir().join(format!("p2p_node_stats_journal_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("journal.jsonl");
+    let filename = path.to_str().unwrap();
+    let stats = Stats::new(100, "1".to_string());
+    stats.add_ping("2".to_string(), std::time::Duration::from_millis(100));
+    stats.append_to_journal(filename, 1 << 20).unwrap();
+    stats.append_to_journal(filename, 1 << 20).unwrap();
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"{\"node_id\":\"1\",\"tak").unwrap();
+    assert_eq!(StatsSnapshot::read_journal(filename).unwrap().len(), 2);
+
+    stats.append_to_journal(filename, 1 << 20).unwrap();
+    let snapshots = StatsSnapshot::read_journal(filename).unwrap();
+    assert_eq!(snapshots.len(), 3);
+    assert!(snapshots[2].peer("2").unwrap().ping.is_some());
+
+    let record_len = fs::metadata(&path).unwrap().len() / 3;
+    stats.append_to_journal(filename, record_len * 2).unwrap();
+    let current = StatsSnapshot::read_journal(filename).unwrap();
+    let previous = StatsSnapshot::read_journal(&format!("{}.1", filename)).unwrap();
+
+    // A complete record is never torn, so it must not be skipped silently
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"{\"node_id\":\"1\"\n").unwrap();
+    let malformed = StatsSnapshot::read_journal(filename);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!((current.len(), previous.len()), (1, 3));
+    assert!(malformed.is_err());
+}
@@ -1,58 +1,717 @@
+mod atomic;
+mod binary;
+mod change_point;
+mod comparison;
+mod confidence;
+mod export;
+mod format;
+mod jitter;
+mod journal;
+mod loss;
+mod matrix;
+mod prometheus;
+mod ranking;
+mod reporter;
+mod robust;
+mod rto;
+mod snapshot;
+mod state;
+mod transmission;
+mod window;
+
+use atomic::write_atomic;
+pub use change_point::{ChangeDetection, ChangePoint, Metric, PageHinkley};
+pub use comparison::{
+    mann_whitney_u, welch_t_test, Comparison, MannWhitneyU, OverallComparison, SnapshotComparison,
+    WelchTTest, DEFAULT_ALPHA,
+};
+pub use confidence::ConfidenceLevel;
+pub use export::ReportFormat;
+pub use jitter::JitterEstimator;
+pub use loss::{wilson_interval, LossSummary};
+pub use matrix::LatencyMatrix;
+pub use prometheus::serve_metrics;
+pub use ranking::RankCriterion;
+pub use reporter::StatsReporter;
+pub use robust::{durations_mad, durations_median, OutlierPolicy};
+pub use rto::{RttEstimator, TimeoutBounds, CLOCK_GRANULARITY, INITIAL_RTO};
+pub use snapshot::{PeerSnapshot, StatsSnapshot};
+pub use transmission::{
+    transmissions_mean_rate, transmissions_rate_error_with_ci, transmissions_rate_quantile,
+    transmissions_rate_std_dev, ThroughputSummary, Transmission,
+};
+use window::wall_clock;
+pub use window::{PushLossy, Sample, Window};
+
 use chashmap::CHashMap;
+use serde::{Deserialize, Serialize};
 use std::{
+    borrow::Borrow,
+    cell::{Cell, RefCell},
+    collections::VecDeque,
     fmt,
-    fs::File,
-    io::{self, prelude::*},
-    time::Duration,
+    hash::Hash,
+    io,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-pub struct Stats {
-    pings_to_peers: CHashMap<String, Vec<Duration>>,
-    transmissions_rates: CHashMap<String, Vec<Duration>>,
+/// Number of latest change points kept per peer
+const MAX_CHANGE_POINTS: usize = 32;
+
+/// Identifier of a peer, printed with its `Display` implementation in reports and exports
+pub trait PeerIdentifier: Hash + Eq + Clone + fmt::Display {}
+
+impl<T: Hash + Eq + Clone + fmt::Display> PeerIdentifier for T {}
+
+pub struct Stats<P = String> {
+    pings_to_peers: CHashMap<P, Window<Duration>>,
+    /// Outcomes of sent pings, `true` for answered ones
+    ping_outcomes: CHashMap<P, Window<bool>>,
+    transmissions_rates: CHashMap<P, Window<Transmission>>,
+    rtt_estimators: CHashMap<P, RttEstimator>,
+    jitter_estimators: CHashMap<P, JitterEstimator>,
+    last_seen: CHashMap<P, Instant>,
+    rtt_change_detectors: CHashMap<P, PageHinkley>,
+    rate_change_detectors: CHashMap<P, PageHinkley>,
+    change_points: CHashMap<P, VecDeque<ChangePoint>>,
+    change_detection: Option<ChangeDetection>,
+    peer_ttl: Option<Duration>,
+    timeout_bounds: TimeoutBounds,
     window_size: usize,
-    peer_id: String,
+    max_age: Option<Duration>,
+    peer_id: P,
+    report_quantiles: Vec<f64>,
+    min_samples: usize,
+    confidence: ConfidenceLevel,
+    outlier_policy: OutlierPolicy,
 }
 
-impl Stats {
-    pub fn new(window_size: usize, peer_id: String) -> Self {
+impl<P: PeerIdentifier> Stats<P> {
+    pub fn new(window_size: usize, peer_id: P) -> Self {
         Self {
             pings_to_peers: CHashMap::new(),
+            ping_outcomes: CHashMap::new(),
             transmissions_rates: CHashMap::new(),
+            rtt_estimators: CHashMap::new(),
+            jitter_estimators: CHashMap::new(),
+            last_seen: CHashMap::new(),
+            rtt_change_detectors: CHashMap::new(),
+            rate_change_detectors: CHashMap::new(),
+            change_points: CHashMap::new(),
+            change_detection: None,
+            peer_ttl: None,
+            timeout_bounds: TimeoutBounds::default(),
             window_size,
+            max_age: None,
             peer_id,
+            report_quantiles: Vec::new(),
+            min_samples: 1,
+            confidence: ConfidenceLevel::default(),
+            outlier_policy: OutlierPolicy::default(),
         }
     }
 
+    /// Additionally bound the windows by age, so only samples from the last `max_age` are kept
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Forget peers which had no new samples for `ttl`.
+    /// Idle peers are dropped when queried or reported, or explicitly by [`Stats::prune`].
+    pub fn with_peer_ttl(mut self, ttl: Duration) -> Self {
+        self.peer_ttl = Some(ttl);
+        self
+    }
+
+    /// Clamp timeouts returned by [`Stats::recommended_timeout`] to `[min, max]`
+    pub fn with_timeout_bounds(mut self, min: Duration, max: Duration) -> Self {
+        self.timeout_bounds = TimeoutBounds { min, max };
+        self
+    }
+
+    /// Include the given quantiles (e.g. `0.5`, `0.99`) of each peer window in the report
+    pub fn with_report_quantiles(mut self, quantiles: Vec<f64>) -> Self {
+        self.report_quantiles = quantiles;
+        self
+    }
+
+    /// Leave peers with fewer than `min_samples` samples out of [`Stats::rank_peers`]
+    pub fn with_min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Confidence level of the reported intervals, 95% by default
+    pub fn with_confidence_level(mut self, confidence: ConfidenceLevel) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// Exclude round trip time outliers from the reported means and intervals
+    pub fn with_outlier_policy(mut self, outlier_policy: OutlierPolicy) -> Self {
+        self.outlier_policy = outlier_policy;
+        self
+    }
+
+    /// Detect shifts of round trip times and transmission rates to new levels
+    pub fn with_change_detection(mut self, change_detection: ChangeDetection) -> Self {
+        self.change_detection = Some(change_detection);
+        self
+    }
+
+    /// Replaces the file with the report atomically, so readers never see a partial report
     pub fn save_to_file(&self, filename: &str) -> io::Result<()> {
-        let mut file = File::create(filename)?;
-        file.write_all(self.to_string().as_bytes())?;
-        Ok(())
+        self.save_as(filename, ReportFormat::Text)
+    }
+
+    pub fn add_ping(&self, peer_id: P, rtt: Duration) {
+        self.add_ping_sample(peer_id, rtt, Instant::now(), SystemTime::now())
+    }
+
+    /// Adds a ping whose round trip completed at `at`
+    pub fn add_ping_at(&self, peer_id: P, rtt: Duration, at: Instant) {
+        self.add_ping_sample(peer_id, rtt, at, wall_clock(at))
+    }
+
+    /// Records a ping to `peer_id` which was not answered in time
+    pub fn add_ping_timeout(&self, peer_id: P) {
+        self.push_sample(
+            &self.ping_outcomes,
+            &peer_id,
+            false,
+            Instant::now(),
+            SystemTime::now(),
+        )
     }
 
-    pub fn add_ping(&self, peer_id: String, rtt: Duration) {
-        if !self.pings_to_peers.contains_key(&peer_id) {
-            self.pings_to_peers.insert_new(peer_id.clone(), Vec::new())
+    /// Records a ping to `peer_id` which timed out at `at`
+    pub fn add_ping_timeout_at(&self, peer_id: P, at: Instant) {
+        self.push_sample(&self.ping_outcomes, &peer_id, false, at, wall_clock(at))
+    }
+
+    pub fn add_transmission(&self, peer_id: P, time: Duration, n_bytes: u32) {
+        self.add_transmission_sample(peer_id, time, n_bytes, Instant::now(), SystemTime::now())
+    }
+
+    /// Adds a transmission of `n_bytes` which took `time` and completed at `at`.
+    /// Transmissions of zero bytes or taking zero time carry no rate information and are ignored.
+    pub fn add_transmission_at(&self, peer_id: P, time: Duration, n_bytes: u32, at: Instant) {
+        self.add_transmission_sample(peer_id, time, n_bytes, at, wall_clock(at))
+    }
+
+    /// Adds a ping completed at the monotonic time `at` and the wall-clock time `taken_at`
+    fn add_ping_sample(&self, peer_id: P, rtt: Duration, at: Instant, taken_at: SystemTime) {
+        upsert_peer(
+            &self.rtt_estimators,
+            &peer_id,
+            || RttEstimator::new(rtt),
+            |estimator| estimator.update(rtt),
+        );
+        upsert_peer(
+            &self.jitter_estimators,
+            &peer_id,
+            || JitterEstimator::new(rtt),
+            |estimator| estimator.update(rtt),
+        );
+        self.detect_change(
+            &self.rtt_change_detectors,
+            &peer_id,
+            Metric::Rtt,
+            rtt.as_secs_f64(),
+            taken_at,
+        );
+        self.push_sample(&self.ping_outcomes, &peer_id, true, at, taken_at);
+        self.push_sample(&self.pings_to_peers, &peer_id, rtt, at, taken_at)
+    }
+
+    fn add_transmission_sample(
+        &self,
+        peer_id: P,
+        time: Duration,
+        n_bytes: u32,
+        at: Instant,
+        taken_at: SystemTime,
+    ) {
+        let transmission = Transmission {
+            n_bytes: n_bytes.into(),
+            elapsed: time,
+        };
+        if transmission.is_measurable() {
+            self.detect_change(
+                &self.rate_change_detectors,
+                &peer_id,
+                Metric::TransmissionRate,
+                transmission.rate(),
+                taken_at,
+            );
+            self.push_sample(
+                &self.transmissions_rates,
+                &peer_id,
+                transmission,
+                at,
+                taken_at,
+            )
         }
-        self.pings_to_peers
-            .get_mut(&peer_id)
-            .expect("Failed to get peer entry")
-            .push_lossy(rtt, self.window_size)
     }
 
-    pub fn add_transmission(&self, peer_id: String, time: Duration, n_bytes: u32) {
-        if !self.transmissions_rates.contains_key(&peer_id) {
-            self.transmissions_rates
-                .insert_new(peer_id.clone(), Vec::new())
+    /// Summary of the round trip times to `peer_id` currently in the window
+    pub fn ping_summary<Q>(&self, peer_id: &Q) -> Option<Summary>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let summary = self.summarize(&self.window_values(&self.pings_to_peers, peer_id)?)?;
+        Some(Summary {
+            jitter: self.ping_jitter(peer_id),
+            ..summary
+        })
+    }
+
+    /// Share of pings to `peer_id` in the window which timed out
+    pub fn ping_loss<Q>(&self, peer_id: &Q) -> Option<LossSummary>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        LossSummary::from_outcomes(
+            &self.window_values(&self.ping_outcomes, peer_id)?,
+            self.confidence,
+        )
+    }
+
+    /// Summary of the transmission rates to `peer_id` currently in the window
+    pub fn transmission_summary<Q>(&self, peer_id: &Q) -> Option<ThroughputSummary>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.summarize_transmissions(&self.window_values(&self.transmissions_rates, peer_id)?)
+    }
+
+    /// Round trip times to `peer_id` in the window taken from `from` to `to` inclusive.
+    /// Samples which were already dropped from the window are not included.
+    pub fn samples_between<Q>(
+        &self,
+        peer_id: &Q,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Vec<Sample<Duration>>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.window_samples_between(&self.pings_to_peers, peer_id, from, to)
+            .unwrap_or_default()
+    }
+
+    /// Transmissions to `peer_id` in the window taken from `from` to `to` inclusive
+    pub fn transmissions_between<Q>(
+        &self,
+        peer_id: &Q,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Vec<Sample<Transmission>>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.window_samples_between(&self.transmissions_rates, peer_id, from, to)
+            .unwrap_or_default()
+    }
+
+    /// Summary of the round trip times to `peer_id` taken from `from` to `to` inclusive,
+    /// with the jitter computed over the same samples
+    pub fn ping_summary_between<Q>(
+        &self,
+        peer_id: &Q,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Option<Summary>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let pings: Vec<Duration> = self
+            .samples_between(peer_id, from, to)
+            .into_iter()
+            .map(|sample| sample.value)
+            .collect();
+        let summary = self.summarize(&pings)?;
+        let mut estimator = JitterEstimator::new(pings[0]);
+        pings[1..].iter().for_each(|&rtt| estimator.update(rtt));
+        Some(Summary {
+            jitter: Some(estimator.jitter()).filter(|_| pings.len() > 1),
+            ..summary
+        })
+    }
+
+    /// Share of pings to `peer_id` sent from `from` to `to` inclusive which timed out
+    pub fn ping_loss_between<Q>(
+        &self,
+        peer_id: &Q,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Option<LossSummary>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let outcomes: Vec<bool> = self
+            .window_samples_between(&self.ping_outcomes, peer_id, from, to)?
+            .into_iter()
+            .map(|sample| sample.value)
+            .collect();
+        LossSummary::from_outcomes(&outcomes, self.confidence)
+    }
+
+    /// Summary of the transmission rates to `peer_id` taken from `from` to `to` inclusive
+    pub fn transmission_summary_between<Q>(
+        &self,
+        peer_id: &Q,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Option<ThroughputSummary>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let transmissions: Vec<Transmission> = self
+            .transmissions_between(peer_id, from, to)
+            .into_iter()
+            .map(|sample| sample.value)
+            .collect();
+        self.summarize_transmissions(&transmissions)
+    }
+
+    /// Quantile `q` in `[0, 1]` of the round trip times to `peer_id` currently in the window
+    pub fn ping_quantile<Q>(&self, peer_id: &Q, q: f64) -> Option<Duration>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        durations_quantile(&self.window_values(&self.pings_to_peers, peer_id)?, q)
+    }
+
+    /// Quantile `q` in `[0, 1]` of the transmission rates to `peer_id` in bytes per second
+    pub fn transmission_quantile<Q>(&self, peer_id: &Q, q: f64) -> Option<f64>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        transmissions_rate_quantile(&self.window_values(&self.transmissions_rates, peer_id)?, q)
+    }
+
+    /// Smoothed round trip time estimate of `peer_id` over all pings, not only the window
+    pub fn rtt_estimate<Q>(&self, peer_id: &Q) -> Option<RttEstimator>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.expire_peer_if_idle(peer_id, Instant::now());
+        self.rtt_estimators.get(peer_id).map(|estimator| *estimator)
+    }
+
+    /// RFC 3550 interarrival jitter of the round trip times to `peer_id` over all pings
+    pub fn ping_jitter<Q>(&self, peer_id: &Q) -> Option<Duration>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.expire_peer_if_idle(peer_id, Instant::now());
+        self.jitter_estimators
+            .get(peer_id)
+            .map(|estimator| estimator.jitter())
+    }
+
+    /// Retransmission timeout for requests to `peer_id` computed as in RFC 6298.
+    /// Returns the initial timeout of 1 second when no pings to the peer were measured.
+    pub fn recommended_timeout<Q>(&self, peer_id: &Q) -> Duration
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.expire_peer_if_idle(peer_id, Instant::now());
+        match self.rtt_estimators.get(peer_id) {
+            Some(estimator) => estimator.rto(&self.timeout_bounds),
+            None => self.timeout_bounds.clamp(INITIAL_RTO),
         }
-        self.transmissions_rates
-            .get_mut(&peer_id)
-            .expect("Failed to get peer entry")
-            .push_lossy(
-                //put transmission rate which is elapsed time per byte
-                time / n_bytes,
-                self.window_size,
-            )
     }
+
+    /// Latest shifts of the metrics of `peer_id` to new levels, from the oldest to the newest.
+    /// Only detected when enabled by [`Stats::with_change_detection`].
+    pub fn change_points<Q>(&self, peer_id: &Q) -> Vec<ChangePoint>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.expire_peer_if_idle(peer_id, Instant::now());
+        self.change_points
+            .get(peer_id)
+            .map(|change_points| change_points.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Time the last sample of `peer_id` was taken
+    pub fn last_seen<Q>(&self, peer_id: &Q) -> Option<Instant>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.last_seen.get(peer_id).map(|at| *at)
+    }
+
+    /// Removes all samples and estimates of `peer_id`
+    pub fn remove_peer<Q>(&self, peer_id: &Q)
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        // Forgotten first, so that a concurrent expiry doesn't see the peer while it is removed
+        self.last_seen.remove(peer_id);
+        self.remove_samples(peer_id);
+    }
+
+    fn remove_samples<Q>(&self, peer_id: &Q)
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.pings_to_peers.remove(peer_id);
+        self.ping_outcomes.remove(peer_id);
+        self.transmissions_rates.remove(peer_id);
+        self.rtt_estimators.remove(peer_id);
+        self.jitter_estimators.remove(peer_id);
+        self.rtt_change_detectors.remove(peer_id);
+        self.rate_change_detectors.remove(peer_id);
+        self.change_points.remove(peer_id);
+    }
+
+    /// Removes peers idle for longer than the TTL set by [`Stats::with_peer_ttl`],
+    /// returning their ids
+    pub fn prune(&self) -> Vec<P> {
+        if self.peer_ttl.is_none() {
+            return Vec::new();
+        }
+        let now = Instant::now();
+        // Idleness is checked under the lock of each entry, a peer seen meanwhile is kept
+        let idle = RefCell::new(Vec::new());
+        self.last_seen.retain(|peer_id, at| {
+            let expired = self.is_idle(*at, now);
+            if expired {
+                idle.borrow_mut().push(peer_id.clone());
+            }
+            !expired
+        });
+        let idle = idle.into_inner();
+        for peer_id in &idle {
+            self.remove_samples(peer_id);
+        }
+        idle
+    }
+
+    fn is_idle(&self, last_seen: Instant, now: Instant) -> bool {
+        match self.peer_ttl {
+            Some(ttl) => now.saturating_duration_since(last_seen) > ttl,
+            None => false,
+        }
+    }
+
+    fn expire_peer_if_idle<Q>(&self, peer_id: &Q, now: Instant)
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idle = match self.last_seen.get(peer_id) {
+            Some(at) => self.is_idle(*at, now),
+            None => false,
+        };
+        if !idle {
+            return;
+        }
+        // Checked again under the lock of the entry, in case the peer was seen meanwhile
+        let expired = Cell::new(false);
+        self.last_seen.retain(|seen_id, at| {
+            let expire = seen_id.borrow() == peer_id && self.is_idle(*at, now);
+            expired.set(expired.get() || expire);
+            !expire
+        });
+        if expired.get() {
+            self.remove_samples(peer_id);
+        }
+    }
+
+    fn touch_peer(&self, peer_id: &P, at: Instant) {
+        upsert_peer(
+            &self.last_seen,
+            peer_id,
+            || at,
+            |last_seen| *last_seen = at.max(*last_seen),
+        );
+    }
+
+    fn detect_change(
+        &self,
+        detectors: &CHashMap<P, PageHinkley>,
+        peer_id: &P,
+        metric: Metric,
+        value: f64,
+        taken_at: SystemTime,
+    ) {
+        let config = match self.change_detection {
+            Some(config) => config,
+            None => return,
+        };
+        let shift = Cell::new(None);
+        let update = |detector: &mut PageHinkley| shift.set(detector.update(value));
+        upsert_peer(
+            detectors,
+            peer_id,
+            || {
+                let mut detector = PageHinkley::new(config);
+                update(&mut detector);
+                detector
+            },
+            update,
+        );
+        if let Some((before, after)) = shift.get() {
+            let change_point = ChangePoint {
+                metric,
+                at: taken_at,
+                before,
+                after,
+            };
+            upsert_peer(
+                &self.change_points,
+                peer_id,
+                || VecDeque::from(vec![change_point]),
+                |change_points| change_points.push_lossy(change_point, MAX_CHANGE_POINTS),
+            );
+        }
+    }
+
+    fn push_sample<T>(
+        &self,
+        map: &CHashMap<P, Window<T>>,
+        peer_id: &P,
+        value: T,
+        at: Instant,
+        taken_at: SystemTime,
+    ) {
+        // Only one of the closures runs, the cell lets both of them own the sample
+        let sample = Cell::new(Some(Sample {
+            value,
+            at,
+            taken_at,
+        }));
+        let push = |window: &mut Window<T>| {
+            if let Some(sample) = sample.take() {
+                window.push_sample(sample)
+            }
+        };
+        upsert_peer(
+            map,
+            peer_id,
+            || {
+                let mut window = self.new_window();
+                push(&mut window);
+                window
+            },
+            push,
+        );
+        self.touch_peer(peer_id, at)
+    }
+
+    fn new_window<T>(&self) -> Window<T> {
+        let window = Window::new(self.window_size);
+        match self.max_age {
+            Some(max_age) => window.with_max_age(max_age),
+            None => window,
+        }
+    }
+
+    /// Samples of `peer_id` left in the window after expiring the outdated ones
+    fn window_values<T: Clone, Q>(
+        &self,
+        map: &CHashMap<P, Window<T>>,
+        peer_id: &Q,
+    ) -> Option<Vec<T>>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let now = Instant::now();
+        self.expire_peer_if_idle(peer_id, now);
+        let mut window = map.get_mut(peer_id)?;
+        window.expire(now);
+        Some(window.to_vec())
+    }
+
+    /// Samples of `peer_id` left in the window taken from `from` to `to` inclusive
+    fn window_samples_between<T: Clone, Q>(
+        &self,
+        map: &CHashMap<P, Window<T>>,
+        peer_id: &Q,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Option<Vec<Sample<T>>>
+    where
+        P: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let now = Instant::now();
+        self.expire_peer_if_idle(peer_id, now);
+        let mut window = map.get_mut(peer_id)?;
+        window.expire(now);
+        Some(window.samples_between(from, to).cloned().collect())
+    }
+
+    fn summarize(&self, durations: &[Duration]) -> Option<Summary> {
+        Summary::from_durations(durations, self.confidence, self.outlier_policy)
+    }
+
+    fn summarize_transmissions(&self, transmissions: &[Transmission]) -> Option<ThroughputSummary> {
+        ThroughputSummary::from_transmissions(transmissions, self.confidence, self.outlier_policy)
+    }
+
+    fn format_quantiles(&self, durations: &[Duration]) -> String {
+        self.report_quantiles
+            .iter()
+            .filter_map(|&q| {
+                durations_quantile(durations, q)
+                    .map(|duration| format!(" p{}={:?}", percentile_label(q), duration))
+            })
+            .collect()
+    }
+
+    fn format_rate_quantiles(&self, transmissions: &[Transmission]) -> String {
+        self.report_quantiles
+            .iter()
+            .filter_map(|&q| {
+                transmissions_rate_quantile(transmissions, q)
+                    .map(|rate| format!(" p{}={:.1}B/s", percentile_label(q), rate))
+            })
+            .collect()
+    }
+}
+
+/// Updates the value of `peer_id` atomically like [`CHashMap::upsert`],
+/// but clones the key only when a new value is inserted
+pub(crate) fn upsert_peer<P: PeerIdentifier, V>(
+    map: &CHashMap<P, V>,
+    peer_id: &P,
+    insert: impl FnOnce() -> V,
+    update: impl FnOnce(&mut V),
+) {
+    match map.get_mut(peer_id) {
+        Some(mut value) => update(&mut value),
+        None => map.upsert(peer_id.clone(), insert, update),
+    }
+}
+
+/// Percentile of the quantile `q` rounded to hide floating point artifacts, e.g. `29` for `0.29`
+fn percentile_label(q: f64) -> f64 {
+    (q * 100.0 * 1000.0).round() / 1000.0
 }
 
 #[test]
@@ -66,6 +725,21 @@ fn correctly_added_pings() {
     assert_eq!(peer_2_pings.len(), 2)
 }
 
+#[test]
+fn keyed_by_native_peer_ids() {
+    use std::net::SocketAddr;
+
+    let node: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+    let peer: SocketAddr = "10.0.0.2:4000".parse().unwrap();
+    let stats = Stats::new(100, node);
+    stats.add_ping(peer, Duration::from_secs(1));
+    assert_eq!(stats.ping_summary(&peer).unwrap().n_samples, 1);
+    assert!(stats
+        .to_string()
+        .contains("\"10.0.0.2:4000\" 1s (n=1, too few samples for an interval)"));
+    assert_eq!(stats.snapshot(false).peers[0].peer_id, "10.0.0.2:4000");
+}
+
 #[test]
 fn correctly_added_transmissions() {
     let stats = Stats::new(100, "1".to_string());
@@ -77,7 +751,275 @@ fn correctly_added_transmissions() {
     assert_eq!(peer_2_transmissions.len(), 2)
 }
 
-fn durations_mean(durations: &Vec<Duration>) -> Option<Duration> {
+#[test]
+fn unmeasurable_transmissions_are_ignored() {
+    let stats = Stats::new(100, "1".to_string());
+    stats.add_transmission("2".to_string(), Duration::from_secs(1), 0);
+    stats.add_transmission("2".to_string(), Duration::from_secs(0), 100);
+    assert!(stats.transmission_summary("2").is_none());
+    stats.add_transmission("2".to_string(), Duration::from_nanos(1), 10_000_000);
+    let summary = stats.transmission_summary("2").unwrap();
+    assert_eq!(summary.mean, 1e16);
+    assert_eq!(summary.n_bytes, 10_000_000);
+}
+
+/// Statistics computed over a window of durations.
+/// The mean, standard deviation and interval leave out samples considered outliers.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub mean: Duration,
+    pub std_dev: Duration,
+    /// Half-width of the confidence interval of the mean, unknown for less than 2 samples
+    pub error: Option<Duration>,
+    #[serde(default)]
+    pub confidence: ConfidenceLevel,
+    /// Number of samples in the window, including outliers
+    pub n_samples: usize,
+    #[serde(default)]
+    pub n_outliers: usize,
+    pub min: Duration,
+    pub max: Duration,
+    #[serde(default)]
+    pub median: Duration,
+    /// Median absolute deviation from the median
+    #[serde(default)]
+    pub mad: Duration,
+    /// RFC 3550 interarrival jitter, only known for round trip times of a peer
+    #[serde(default)]
+    pub jitter: Option<Duration>,
+}
+
+impl Summary {
+    pub fn from_durations(
+        durations: &[Duration],
+        confidence: ConfidenceLevel,
+        outlier_policy: OutlierPolicy,
+    ) -> Option<Self> {
+        let retained = outlier_policy.retain(durations);
+        Some(Self {
+            mean: durations_mean(&retained)?,
+            std_dev: durations_std_dev(&retained)?,
+            error: durations_error_with_ci(&retained, confidence),
+            confidence,
+            n_samples: durations.len(),
+            n_outliers: durations.len() - retained.len(),
+            min: *durations.iter().min()?,
+            max: *durations.iter().max()?,
+            median: durations_median(durations)?,
+            mad: durations_mad(durations)?,
+            jitter: None,
+        })
+    }
+}
+
+#[test]
+fn expired_samples_are_dropped() {
+    let stats = Stats::new(100, "1".to_string()).with_max_age(Duration::from_secs(60));
+    let now = Instant::now();
+    let Some(long_ago) = now.checked_sub(Duration::from_secs(120)) else {
+        return;
+    };
+    stats.add_ping_at("2".to_string(), Duration::from_secs(9), long_ago);
+    stats.add_ping_at("2".to_string(), Duration::from_secs(1), now);
+    stats.add_ping_at("3".to_string(), Duration::from_secs(1), long_ago);
+    assert_eq!(stats.pings_to_peers.get("2").unwrap().len(), 1);
+    assert_eq!(stats.ping_summary("2").unwrap().n_samples, 1);
+    assert_eq!(stats.pings_to_peers.get("3").unwrap().len(), 1);
+    assert!(stats.ping_summary("3").is_none());
+}
+
+#[test]
+fn age_bounded_windows_need_no_count_bound() {
+    let stats = Stats::new(usize::MAX, "1".to_string()).with_max_age(Duration::from_secs(60));
+    stats.add_ping("2".to_string(), Duration::from_secs(1));
+    assert_eq!(stats.ping_summary("2").unwrap().n_samples, 1);
+}
+
+#[test]
+fn idle_peers_are_pruned() {
+    let stats = Stats::new(100, "1".to_string()).with_peer_ttl(Duration::from_secs(60));
+    let now = Instant::now();
+    let Some(long_ago) = now.checked_sub(Duration::from_secs(120)) else {
+        return;
+    };
+    stats.add_ping_at("2".to_string(), Duration::from_secs(1), long_ago);
+    stats.add_ping_at("3".to_string(), Duration::from_secs(1), long_ago);
+    stats.add_transmission_at("3".to_string(), Duration::from_secs(1), 1, now);
+    stats.add_ping("4".to_string(), Duration::from_secs(1));
+    assert_eq!(stats.last_seen("3"), Some(now));
+    assert_eq!(stats.prune(), vec!["2".to_string()]);
+    assert!(stats.ping_summary("2").is_none());
+    assert!(stats.rtt_estimate("2").is_none());
+
+    stats.remove_peer("4");
+    assert!(stats.ping_summary("4").is_none());
+    assert!(stats.last_seen("4").is_none());
+    assert!(stats.ping_summary("3").is_some());
+}
+
+#[test]
+fn correct_ping_loss() {
+    let stats = Stats::new(4, "1".to_string());
+    stats.add_ping_timeout("2".to_string());
+    stats.add_ping("2".to_string(), Duration::from_secs(1));
+    stats.add_ping_timeout("2".to_string());
+    stats.add_ping("2".to_string(), Duration::from_secs(1));
+    stats.add_ping("2".to_string(), Duration::from_secs(1));
+    let loss = stats.ping_loss("2").unwrap();
+    assert_eq!(loss.n_sent, 4);
+    assert_eq!(loss.n_lost, 1);
+    assert_eq!(loss.rate, 0.25);
+    assert!(loss.ci_low < 0.25 && loss.ci_high > 0.25);
+    assert!(stats.to_string().contains("\"2\" 25.0% ["));
+}
+
+#[test]
+fn correct_ping_jitter() {
+    let stats = Stats::new(100, "1".to_string());
+    assert!(stats.ping_jitter("2").is_none());
+    stats.add_ping("2".to_string(), Duration::from_millis(100));
+    stats.add_ping("2".to_string(), Duration::from_millis(260));
+    assert_eq!(stats.ping_jitter("2"), Some(Duration::from_millis(10)));
+    assert_eq!(
+        stats.ping_summary("2").unwrap().jitter,
+        Some(Duration::from_millis(10))
+    );
+    assert!(stats.to_string().contains(" jitter=10ms"));
+}
+
+#[test]
+fn correct_recommended_timeout() {
+    let stats = Stats::new(100, "1".to_string())
+        .with_timeout_bounds(Duration::from_millis(200), Duration::from_secs(10));
+    assert_eq!(stats.recommended_timeout("2"), INITIAL_RTO);
+    stats.add_ping("2".to_string(), Duration::from_millis(100));
+    stats.add_ping("2".to_string(), Duration::from_millis(100));
+    let estimate = stats.rtt_estimate("2").unwrap();
+    assert_eq!(estimate.srtt(), Duration::from_millis(100));
+    assert_eq!(estimate.rttvar(), Duration::from_micros(37500));
+    assert_eq!(stats.recommended_timeout("2"), Duration::from_millis(250));
+}
+
+#[test]
+fn outliers_are_excluded_from_mean() {
+    let stats =
+        Stats::new(100, "1".to_string()).with_outlier_policy(OutlierPolicy::Mad { threshold: 3.0 });
+    for millis in 10..20 {
+        stats.add_ping("2".to_string(), Duration::from_millis(millis));
+    }
+    stats.add_ping("2".to_string(), Duration::from_secs(5));
+    let summary = stats.ping_summary("2").unwrap();
+    assert_eq!(summary.n_samples, 11);
+    assert_eq!(summary.n_outliers, 1);
+    assert_eq!(summary.mean, Duration::from_micros(14500));
+    assert_eq!(summary.median, Duration::from_millis(15));
+    assert_eq!(summary.max, Duration::from_secs(5));
+    assert!(stats.to_string().contains("(n=10) outliers=1"));
+}
+
+#[test]
+fn transmission_outliers_are_reported() {
+    let stats =
+        Stats::new(100, "1".to_string()).with_outlier_policy(OutlierPolicy::Mad { threshold: 3.0 });
+    for n_bytes in 1000..1010 {
+        stats.add_transmission("2".to_string(), Duration::from_secs(1), n_bytes);
+    }
+    stats.add_transmission("2".to_string(), Duration::from_secs(1), 100_000);
+    let summary = stats.transmission_summary("2").unwrap();
+    assert_eq!(summary.n_outliers, 1);
+    assert!(stats.to_string().contains(" B/s (n=10) outliers=1"));
+}
+
+#[test]
+fn samples_carry_the_wall_clock_time() {
+    let stats = Stats::new(100, "1".to_string());
+    let before = SystemTime::now();
+    stats.add_ping("2".to_string(), Duration::from_millis(100));
+    let after = SystemTime::now();
+    let samples = stats.samples_between("2", before, after);
+    assert_eq!(samples.len(), 1);
+}
+
+#[test]
+fn correct_summaries_between() {
+    let stats = Stats::new(100, "1".to_string());
+    let start = Instant::now();
+    let millis = [100, 200, 300, 400];
+    for (i, &rtt) in millis.iter().enumerate() {
+        let at = start + Duration::from_secs(i as u64 * 60);
+        stats.add_ping_at("2".to_string(), Duration::from_millis(rtt), at);
+        stats.add_transmission_at("2".to_string(), Duration::from_millis(rtt), 1000, at);
+    }
+    stats.add_ping_timeout_at("2".to_string(), start + Duration::from_secs(90));
+    let samples = stats.samples_between(
+        "2",
+        UNIX_EPOCH,
+        SystemTime::now() + Duration::from_secs(3600),
+    );
+    assert_eq!(samples.len(), 4);
+    let from = samples[1].taken_at;
+    let to = samples[2].taken_at;
+    assert_eq!(to.duration_since(from).unwrap(), Duration::from_secs(60));
+
+    let between = stats.samples_between("2", from, to);
+    let values: Vec<Duration> = between.iter().map(|sample| sample.value).collect();
+    assert_eq!(
+        values,
+        vec![Duration::from_millis(200), Duration::from_millis(300)]
+    );
+    let summary = stats.ping_summary_between("2", from, to).unwrap();
+    assert_eq!(summary.mean, Duration::from_millis(250));
+    assert_eq!(summary.n_samples, 2);
+    assert_eq!(summary.jitter, Some(Duration::from_micros(6250)));
+    let loss = stats.ping_loss_between("2", from, to).unwrap();
+    assert_eq!((loss.n_sent, loss.n_lost), (3, 1));
+    let throughput = stats.transmission_summary_between("2", from, to).unwrap();
+    assert_eq!(throughput.n_samples, 2);
+    assert_eq!(stats.transmissions_between("2", from, to).len(), 2);
+    assert!(stats
+        .ping_summary_between("2", UNIX_EPOCH, UNIX_EPOCH)
+        .is_none());
+    assert!(stats.samples_between("3", from, to).is_empty());
+}
+
+#[test]
+fn detects_rtt_level_shift() {
+    let stats = Stats::new(100, "1".to_string()).with_change_detection(ChangeDetection::default());
+    for _ in 0..50 {
+        stats.add_ping("2".to_string(), Duration::from_millis(100));
+    }
+    assert!(stats.change_points("2").is_empty());
+    for _ in 0..50 {
+        stats.add_ping("2".to_string(), Duration::from_millis(200));
+    }
+    let change_points = stats.change_points("2");
+    assert_eq!(change_points.len(), 1);
+    assert_eq!(change_points[0].metric, Metric::Rtt);
+    assert!((change_points[0].before - 0.1).abs() < 1e-9);
+    assert!(change_points[0].after > 0.15);
+    assert!(stats
+        .to_string()
+        .contains("Change points by peer:\n\"2\" rtt 100ms -> 200ms at="));
+    assert!(Stats::new(100, "1".to_string())
+        .change_points("2")
+        .is_empty());
+}
+
+#[test]
+fn correct_summary() {
+    let stats = Stats::new(100, "1".to_string());
+    assert!(stats.ping_summary("2").is_none());
+    stats.add_ping("2".to_string(), Duration::from_secs(3));
+    stats.add_ping("2".to_string(), Duration::from_secs(1));
+    stats.add_ping("2".to_string(), Duration::from_secs(5));
+    let summary = stats.ping_summary("2").unwrap();
+    assert_eq!(summary.mean, Duration::from_secs(3));
+    assert_eq!(summary.n_samples, 3);
+    assert_eq!(summary.min, Duration::from_secs(1));
+    assert_eq!(summary.max, Duration::from_secs(5));
+}
+
+pub fn durations_mean(durations: &[Duration]) -> Option<Duration> {
     if durations.is_empty() {
         None
     } else {
@@ -100,7 +1042,7 @@ fn correct_durations_mean() {
     assert_eq!(durations_mean(&durations).unwrap(), Duration::from_secs(3));
 }
 
-fn durations_std_dev(durations: &Vec<Duration>) -> Option<Duration> {
+pub fn durations_std_dev(durations: &[Duration]) -> Option<Duration> {
     let mean = durations_mean(durations)?.as_secs_f64();
     Some(Duration::from_secs_f64(
         (durations
@@ -123,33 +1065,143 @@ fn correct_durations_std_dev() {
     assert!((std_dev - 1.63).abs() < epsilon);
 }
 
-/// Durations mean error with confidence interval of 95%
-/// For correct estimation `durations.len()` should be at least `30`.
-fn durations_error_with_ci(durations: &Vec<Duration>) -> Option<Duration> {
-    // Z-value for 95 percent confidence interval
-    let z = 1.96;
-    let std_dev = durations_std_dev(durations)?;
+/// Quantile `q` in `[0, 1]` of values, linearly interpolated between the closest ranks
+pub fn quantile(values: &[f64], q: f64) -> Option<f64> {
+    if values.is_empty() || !(0.0..=1.0).contains(&q) {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let rank = q * (sorted.len() - 1) as f64;
+    let lower = sorted[rank.floor() as usize];
+    let upper = sorted[rank.ceil() as usize];
+    Some(lower + (upper - lower) * rank.fract())
+}
+
+/// Quantile `q` in `[0, 1]` of durations, linearly interpolated between the closest ranks
+pub fn durations_quantile(durations: &[Duration], q: f64) -> Option<Duration> {
+    let secs: Vec<f64> = durations.iter().map(Duration::as_secs_f64).collect();
+    quantile(&secs, q).map(Duration::from_secs_f64)
+}
+
+#[test]
+fn report_includes_quantiles() {
+    let stats = Stats::new(100, "1".to_string()).with_report_quantiles(vec![0.5, 0.29, 0.99]);
+    stats.add_ping("2".to_string(), Duration::from_secs(1));
+    stats.add_ping("2".to_string(), Duration::from_secs(3));
+    let report = stats.to_string();
+    assert!(report.contains(" p50=2s"));
+    assert!(report.contains(" p29="));
+    assert!(report.contains(" p99="));
+}
+
+#[test]
+fn correct_durations_quantile() {
+    let durations = vec![
+        Duration::from_secs(5),
+        Duration::from_secs(1),
+        Duration::from_secs(3),
+        Duration::from_secs(2),
+        Duration::from_secs(4),
+    ];
+    assert_eq!(
+        durations_quantile(&durations, 0.0),
+        Some(Duration::from_secs(1))
+    );
+    assert_eq!(
+        durations_quantile(&durations, 0.5),
+        Some(Duration::from_secs(3))
+    );
+    assert_eq!(
+        durations_quantile(&durations, 1.0),
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(
+        durations_quantile(&durations, 0.9),
+        Some(Duration::from_secs_f64(4.6))
+    );
+    assert_eq!(durations_quantile(&durations, 1.5), None);
+    assert_eq!(durations_quantile(&[], 0.5), None);
+}
+
+/// Half-width of the confidence interval of the durations mean, based on Student's t-distribution
+/// and the sample standard deviation. There is no interval for less than `2` durations.
+pub fn durations_error_with_ci(
+    durations: &[Duration],
+    confidence: ConfidenceLevel,
+) -> Option<Duration> {
+    let n = durations.len();
+    let t = confidence.t(n.checked_sub(1)?)?;
+    let sample_std_dev =
+        durations_std_dev(durations)?.as_secs_f64() * (n as f64 / (n - 1) as f64).sqrt();
     Some(Duration::from_secs_f64(
-        z * std_dev.as_secs_f64() / (durations.len() as f64).sqrt(),
+        t * sample_std_dev / (n as f64).sqrt(),
     ))
 }
 
-impl fmt::Display for Stats {
+#[test]
+fn correct_durations_error_with_ci() {
+    let durations = vec![
+        Duration::from_secs(1),
+        Duration::from_secs(3),
+        Duration::from_secs(5),
+    ];
+    let epsilon = 0.01;
+    let error = durations_error_with_ci(&durations, ConfidenceLevel::P95).unwrap();
+    assert!((error.as_secs_f64() - 4.97).abs() < epsilon);
+    let error = durations_error_with_ci(&durations, ConfidenceLevel::P90).unwrap();
+    assert!((error.as_secs_f64() - 3.37).abs() < epsilon);
+    assert_eq!(
+        durations_error_with_ci(&durations[..1], ConfidenceLevel::P95),
+        None
+    );
+}
+
+/// Formats the interval half-width and unit together with the number of samples
+/// the interval is based on
+fn format_interval(error: Option<String>, unit: &str, n_samples: usize) -> String {
+    match error {
+        Some(error) => format!("±{}{} (n={})", error, unit, n_samples),
+        None => format!(
+            "{} (n={}, too few samples for an interval)",
+            unit, n_samples
+        ),
+    }
+}
+
+impl<P: PeerIdentifier> fmt::Display for Stats<P> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.prune();
+        let now = Instant::now();
         let ping_by_peer: String = self
             .pings_to_peers
             .clone()
             .into_iter()
-            .map(|(peer, durations)| {
-                match (
-                    durations_mean(&durations),
-                    durations_error_with_ci(&durations),
-                ) {
-                    (Some(duration), Some(error)) => {
-                        format!("{:?} {:?}±{:?}\n", peer, duration, error)
-                    }
-                    _ => format!("No ping data for peer {:?}", peer),
-                }
+            .map(|(peer, mut window)| {
+                window.expire(now);
+                (peer, window.to_vec())
+            })
+            .map(|(peer, durations)| match self.summarize(&durations) {
+                Some(summary) => format!(
+                    "{:?} {:?}{}{}{}{}\n",
+                    peer.to_string(),
+                    summary.mean,
+                    format_interval(
+                        summary.error.map(|error| format!("{:?}", error)),
+                        "",
+                        summary.n_samples - summary.n_outliers
+                    ),
+                    match summary.n_outliers {
+                        0 => String::new(),
+                        n_outliers => format!(" outliers={}", n_outliers),
+                    },
+                    self.format_quantiles(&durations),
+                    self.jitter_estimators
+                        .get(&peer)
+                        .map(|estimator| format!(" jitter={:?}", estimator.jitter()))
+                        .unwrap_or_default()
+                ),
+                None => format!("No ping data for peer {:?}\n", peer.to_string()),
             })
             .collect();
 
@@ -157,44 +1209,84 @@ impl fmt::Display for Stats {
             .transmissions_rates
             .clone()
             .into_iter()
-            .map(|(peer, durations)| {
-                match (
-                    durations_mean(&durations),
-                    durations_error_with_ci(&durations),
-                ) {
-                    (Some(duration), Some(error)) => {
-                        format!("{:?} {:?}±{:?} per byte\n", peer, duration, error)
-                    }
-                    _ => format!("No transmission data for peer {:?}", peer),
-                }
+            .map(|(peer, mut window)| {
+                window.expire(now);
+                (peer, window.to_vec())
+            })
+            .map(
+                |(peer, transmissions)| match self.summarize_transmissions(&transmissions) {
+                    Some(summary) => format!(
+                        "{:?} {:.1}{}{}{}\n",
+                        peer.to_string(),
+                        summary.mean,
+                        format_interval(
+                            summary.error.map(|error| format!("{:.1}", error)),
+                            " B/s",
+                            summary.n_samples - summary.n_outliers
+                        ),
+                        match summary.n_outliers {
+                            0 => String::new(),
+                            n_outliers => format!(" outliers={}", n_outliers),
+                        },
+                        self.format_rate_quantiles(&transmissions)
+                    ),
+                    None => format!("No transmission data for peer {:?}\n", peer.to_string()),
+                },
+            )
+            .collect();
+
+        let loss_by_peer: String = self
+            .ping_outcomes
+            .clone()
+            .into_iter()
+            .map(|(peer, mut window)| {
+                window.expire(now);
+                (peer, window.to_vec())
+            })
+            .map(
+                |(peer, outcomes)| match LossSummary::from_outcomes(&outcomes, self.confidence) {
+                    Some(loss) => format!(
+                        "{:?} {:.1}% [{:.1}%, {:.1}%] of {}\n",
+                        peer.to_string(),
+                        loss.rate * 100.0,
+                        loss.ci_low * 100.0,
+                        loss.ci_high * 100.0,
+                        loss.n_sent
+                    ),
+                    None => format!("No ping loss data for peer {:?}\n", peer.to_string()),
+                },
+            )
+            .collect();
+        let change_points_by_peer: String = self
+            .change_points
+            .clone()
+            .into_iter()
+            .flat_map(|(peer, change_points)| {
+                change_points.into_iter().map(move |change_point| {
+                    format!(
+                        "{:?} {} {} -> {} at={}\n",
+                        peer.to_string(),
+                        change_point.metric,
+                        change_point.metric.format_value(change_point.before),
+                        change_point.metric.format_value(change_point.after),
+                        change_point
+                            .at
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs()
+                    )
+                })
             })
             .collect();
         write!(
             f,
-            "{:?}\nPing mean for each peer:\n{}Transmission rate mean by peer:\n{}",
-            self.peer_id, ping_by_peer, transmission_rate_by_peer
-        )
-    }
-}
-
-pub trait PushLossy<T> {
-    fn push_lossy(&mut self, element: T, window_size: usize);
-}
-
-impl<T> PushLossy<T> for Vec<T> {
-    fn push_lossy(&mut self, element: T, window_size: usize) {
-        if self.len() >= window_size {
-            self.remove(0);
+            "{:?}\nPing mean for each peer:\n{}Transmission rate mean by peer:\n{}Ping loss by peer:\n{}",
+            self.peer_id.to_string(),
+            ping_by_peer, transmission_rate_by_peer, loss_by_peer
+        )?;
+        if !change_points_by_peer.is_empty() {
+            write!(f, "Change points by peer:\n{}", change_points_by_peer)?;
         }
-        self.push(element);
+        Ok(())
     }
 }
-
-#[test]
-fn correct_push_lossy() {
-    let mut vector = Vec::new();
-    vector.push_lossy(1, 2);
-    vector.push_lossy(2, 2);
-    vector.push_lossy(3, 2);
-    assert_eq!(vector, vec![2, 3]);
-}
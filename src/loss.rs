@@ -0,0 +1,64 @@
+use crate::ConfidenceLevel;
+use serde::{Deserialize, Serialize};
+
+/// Share of pings left unanswered
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LossSummary {
+    pub n_sent: usize,
+    pub n_lost: usize,
+    pub rate: f64,
+    /// Bounds of the Wilson score interval of the loss rate
+    pub ci_low: f64,
+    pub ci_high: f64,
+    #[serde(default)]
+    pub confidence: ConfidenceLevel,
+}
+
+impl LossSummary {
+    /// Summarizes ping outcomes, where `true` stands for an answered ping
+    pub fn from_outcomes(outcomes: &[bool], confidence: ConfidenceLevel) -> Option<Self> {
+        let n_lost = outcomes.iter().filter(|answered| !**answered).count();
+        let (ci_low, ci_high) = wilson_interval(n_lost, outcomes.len(), confidence)?;
+        Some(Self {
+            n_sent: outcomes.len(),
+            n_lost,
+            rate: n_lost as f64 / outcomes.len() as f64,
+            ci_low,
+            ci_high,
+            confidence,
+        })
+    }
+}
+
+/// Wilson score interval for the proportion of `successes` in `n` trials
+pub fn wilson_interval(
+    successes: usize,
+    n: usize,
+    confidence: ConfidenceLevel,
+) -> Option<(f64, f64)> {
+    if n == 0 {
+        return None;
+    }
+    let z = confidence.z();
+    let n = n as f64;
+    let p = successes as f64 / n;
+    let z2 = z.powi(2);
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half_width = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n.powi(2))).sqrt();
+    Some((
+        (center - half_width).max(0.0),
+        (center + half_width).min(1.0),
+    ))
+}
+
+#[test]
+fn correct_wilson_interval() {
+    let epsilon = 0.001;
+    let (low, high) = wilson_interval(1, 10, ConfidenceLevel::P95).unwrap();
+    assert!((low - 0.0179).abs() < epsilon);
+    assert!((high - 0.4042).abs() < epsilon);
+    let (low, high) = wilson_interval(0, 10, ConfidenceLevel::P95).unwrap();
+    assert_eq!(low, 0.0);
+    assert!((high - 0.2775).abs() < epsilon);
+    assert_eq!(wilson_interval(0, 0, ConfidenceLevel::P95), None);
+}
@@ -0,0 +1,171 @@
+use crate::{format::csv_field, PeerIdentifier, Stats, StatsSnapshot, Summary};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+};
+
+/// Network-wide round trip times, where the cell `(from, to)` summarizes
+/// the pings node `from` measured to peer `to`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyMatrix {
+    nodes: BTreeSet<String>,
+    cells: BTreeMap<String, BTreeMap<String, Summary>>,
+}
+
+impl LatencyMatrix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_snapshots<'a, I: IntoIterator<Item = &'a StatsSnapshot>>(snapshots: I) -> Self {
+        let mut matrix = Self::new();
+        for snapshot in snapshots {
+            matrix.add_snapshot(snapshot);
+        }
+        matrix
+    }
+
+    pub fn from_stats<'a, P, I>(stats: I) -> Self
+    where
+        P: PeerIdentifier + 'a,
+        I: IntoIterator<Item = &'a Stats<P>>,
+    {
+        let mut matrix = Self::new();
+        for stats in stats {
+            matrix.add_snapshot(&stats.snapshot(false));
+        }
+        matrix
+    }
+
+    /// Adds the row of `snapshot.node_id`, replacing previously added cells of that row
+    pub fn add_snapshot(&mut self, snapshot: &StatsSnapshot) {
+        self.nodes.insert(snapshot.node_id.clone());
+        let mut row = BTreeMap::new();
+        for peer in &snapshot.peers {
+            self.nodes.insert(peer.peer_id.clone());
+            if let Some(summary) = peer.ping {
+                row.insert(peer.peer_id.clone(), summary);
+            }
+        }
+        self.cells.insert(snapshot.node_id.clone(), row);
+    }
+
+    pub fn get(&self, from: &str, to: &str) -> Option<&Summary> {
+        self.cells.get(from)?.get(to)
+    }
+
+    /// All nodes either reporting stats or seen as peers, sorted
+    pub fn nodes(&self) -> impl Iterator<Item = &String> {
+        self.nodes.iter()
+    }
+
+    /// Pairs of distinct nodes with no ping data from the first to the second
+    pub fn missing(&self) -> Vec<(String, String)> {
+        self.pairs()
+            .filter(|(from, to)| self.get(from, to).is_none())
+            .map(|(from, to)| (from.clone(), to.clone()))
+            .collect()
+    }
+
+    /// One row per pair of distinct nodes with durations in seconds, missing cells are left empty
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("from,to,mean_secs,std_dev_secs,error_secs,n_samples,min_secs,max_secs\n");
+        for (from, to) in self.pairs() {
+            let _ = match self.get(from, to) {
+                Some(summary) => writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{},{}",
+                    csv_field(from),
+                    csv_field(to),
+                    summary.mean.as_secs_f64(),
+                    summary.std_dev.as_secs_f64(),
+                    summary
+                        .error
+                        .map(|error| error.as_secs_f64().to_string())
+                        .unwrap_or_default(),
+                    summary.n_samples,
+                    summary.min.as_secs_f64(),
+                    summary.max.as_secs_f64()
+                ),
+                None => writeln!(csv, "{},{},,,,,,", csv_field(from), csv_field(to)),
+            };
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    fn pairs(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.nodes.iter().flat_map(move |from| {
+            self.nodes
+                .iter()
+                .filter(move |to| *to != from)
+                .map(move |to| (from, to))
+        })
+    }
+}
+
+#[test]
+fn correct_latency_matrix() {
+    use std::time::Duration;
+
+    let node_1 = Stats::new(100, "1".to_string());
+    node_1.add_ping("2".to_string(), Duration::from_millis(10));
+    node_1.add_ping("3".to_string(), Duration::from_millis(30));
+    let node_2 = Stats::new(100, "2".to_string());
+    node_2.add_ping("1".to_string(), Duration::from_millis(20));
+
+    let matrix = LatencyMatrix::from_stats(vec![&node_1, &node_2]);
+    assert_eq!(matrix.nodes().count(), 3);
+    assert_eq!(
+        matrix.get("1", "2").unwrap().mean,
+        Duration::from_millis(10)
+    );
+    assert!(matrix.get("2", "3").is_none());
+    assert_eq!(
+        matrix.missing(),
+        vec![
+            ("2".to_string(), "3".to_string()),
+            ("3".to_string(), "1".to_string()),
+            ("3".to_string(), "2".to_string()),
+        ]
+    );
+    let csv = matrix.to_csv();
+    assert_eq!(csv.lines().count(), 7);
+    assert!(csv.contains("\n1,2,0.01,0,,1,0.01,0.01\n"));
+    assert!(csv.contains("\n3,1,,,,,,\n"));
+    assert_eq!(
+        LatencyMatrix::from_json(&matrix.to_json().unwrap()).unwrap(),
+        matrix
+    );
+}
+
+#[test]
+fn readded_node_replaces_its_row() {
+    use std::time::Duration;
+
+    let node_1 = Stats::new(100, "1".to_string());
+    node_1.add_ping("2".to_string(), Duration::from_millis(10));
+    node_1.add_ping("3".to_string(), Duration::from_millis(30));
+    let mut matrix = LatencyMatrix::from_stats(vec![&node_1]);
+    node_1.remove_peer("2");
+    node_1.add_ping("3".to_string(), Duration::from_millis(50));
+    matrix.add_snapshot(&node_1.snapshot(false));
+
+    assert!(matrix.get("1", "2").is_none());
+    assert_eq!(
+        matrix.get("1", "3").unwrap().mean,
+        Duration::from_millis(40)
+    );
+    assert!(matrix
+        .missing()
+        .contains(&("1".to_string(), "2".to_string())));
+}
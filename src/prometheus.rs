@@ -0,0 +1,192 @@
+use crate::{durations_quantile, transmissions_rate_quantile, PeerIdentifier, Stats};
+use std::{
+    fmt::Write as _,
+    io::{self, prelude::*, BufReader},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// Quantiles exported when no report quantiles were configured
+const DEFAULT_QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+/// Time a scrape may take to send its request or read the response
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of scrapes served concurrently, so that idle clients can not exhaust threads
+const SCRAPE_THREADS: usize = 4;
+
+impl<P: PeerIdentifier> Stats<P> {
+    /// Renders per-peer metrics in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let snapshot = self.snapshot(true);
+        let quantiles = if self.report_quantiles.is_empty() {
+            &DEFAULT_QUANTILES[..]
+        } else {
+            &self.report_quantiles[..]
+        };
+        let node = escape_label(&snapshot.node_id);
+        let mut rtt = String::new();
+        let mut rtt_samples = String::new();
+        let mut rtt_mean = String::new();
+        let mut rate = String::new();
+        let mut rate_samples = String::new();
+        let mut rate_mean = String::new();
+        for peer in &snapshot.peers {
+            let labels = format!("node=\"{}\",peer=\"{}\"", node, escape_label(&peer.peer_id));
+            if let (Some(summary), Some(pings)) = (&peer.ping, &peer.pings) {
+                for &q in quantiles {
+                    if let Some(value) = durations_quantile(pings, q) {
+                        let _ = writeln!(
+                            rtt,
+                            "p2p_ping_rtt_window_seconds{{{},q=\"{}\"}} {}",
+                            labels,
+                            q,
+                            value.as_secs_f64()
+                        );
+                    }
+                }
+                let _ = writeln!(
+                    rtt_samples,
+                    "p2p_ping_rtt_window_samples{{{}}} {}",
+                    labels, summary.n_samples
+                );
+                let _ = writeln!(
+                    rtt_mean,
+                    "p2p_ping_rtt_mean_seconds{{{}}} {}",
+                    labels,
+                    summary.mean.as_secs_f64()
+                );
+            }
+            if let (Some(summary), Some(transmissions)) = (&peer.transmission, &peer.transmissions)
+            {
+                for &q in quantiles {
+                    if let Some(value) = transmissions_rate_quantile(transmissions, q) {
+                        let _ = writeln!(
+                            rate,
+                            "p2p_transmission_rate_window_bytes_per_second{{{},q=\"{}\"}} {}",
+                            labels, q, value
+                        );
+                    }
+                }
+                let _ = writeln!(
+                    rate_samples,
+                    "p2p_transmission_window_samples{{{}}} {}",
+                    labels, summary.n_samples
+                );
+                let _ = writeln!(
+                    rate_mean,
+                    "p2p_transmission_rate_mean_bytes_per_second{{{}}} {}",
+                    labels, summary.mean
+                );
+            }
+        }
+        // The windows forget old samples, so everything is exported as gauges, not as
+        // summaries whose `_sum` and `_count` Prometheus would expect to be monotonic
+        format!(
+            "# HELP p2p_ping_rtt_window_seconds Quantiles of the round trip time to the peer over the window.\n\
+             # TYPE p2p_ping_rtt_window_seconds gauge\n{}\
+             # HELP p2p_ping_rtt_window_samples Number of round trip times to the peer in the window.\n\
+             # TYPE p2p_ping_rtt_window_samples gauge\n{}\
+             # HELP p2p_ping_rtt_mean_seconds Mean round trip time to the peer over the window.\n\
+             # TYPE p2p_ping_rtt_mean_seconds gauge\n{}\
+             # HELP p2p_transmission_rate_window_bytes_per_second Quantiles of the transmission rate to the peer over the window.\n\
+             # TYPE p2p_transmission_rate_window_bytes_per_second gauge\n{}\
+             # HELP p2p_transmission_window_samples Number of transmissions to the peer in the window.\n\
+             # TYPE p2p_transmission_window_samples gauge\n{}\
+             # HELP p2p_transmission_rate_mean_bytes_per_second Byte-weighted mean transmission rate to the peer over the window.\n\
+             # TYPE p2p_transmission_rate_mean_bytes_per_second gauge\n{}",
+            rtt, rtt_samples, rtt_mean, rate, rate_samples, rate_mean
+        )
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[test]
+fn correct_prometheus_rendering() {
+    let stats = Stats::new(100, "node \"1\"".to_string());
+    stats.add_ping("2".to_string(), Duration::from_millis(100));
+    stats.add_ping("2".to_string(), Duration::from_millis(300));
+    stats.add_transmission("3".to_string(), Duration::from_secs(2), 1000);
+    let text = stats.to_prometheus();
+    assert!(text.contains("# TYPE p2p_ping_rtt_window_seconds gauge\n"));
+    assert!(!text.contains(" summary\n"));
+    assert!(text.contains(
+        "p2p_ping_rtt_window_seconds{node=\"node \\\"1\\\"\",peer=\"2\",q=\"0.5\"} 0.2\n"
+    ));
+    assert!(text.contains("p2p_ping_rtt_window_samples{node=\"node \\\"1\\\"\",peer=\"2\"} 2\n"));
+    assert!(!text.contains("_sum{"));
+    assert!(text.contains(
+        "p2p_transmission_rate_mean_bytes_per_second{node=\"node \\\"1\\\"\",peer=\"3\"} 500\n"
+    ));
+}
+
+/// Serves [`Stats::to_prometheus`] at `/metrics` over HTTP from a background thread.
+/// Returns the address the listener is bound to.
+pub fn serve_metrics<P, A>(stats: Arc<Stats<P>>, addr: A) -> io::Result<SocketAddr>
+where
+    P: PeerIdentifier + Send + Sync + 'static,
+    A: ToSocketAddrs,
+{
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    // An idle client delays only the scrapes of its thread, until its read times out
+    for _ in 0..SCRAPE_THREADS {
+        let listener = listener.try_clone()?;
+        let stats = Arc::clone(&stats);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A failing scrape must not stop the listener
+                let _ = respond(&stats, stream);
+            }
+        });
+    }
+    Ok(local_addr)
+}
+
+fn respond<P: PeerIdentifier>(stats: &Stats<P>, mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+    stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers so the client does not see the connection reset
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", stats.to_prometheus()),
+        _ => ("404 Not Found", String::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[test]
+fn serves_metrics_over_http() {
+    let stats = Arc::new(Stats::new(100, "1".to_string()));
+    stats.add_ping("2".to_string(), Duration::from_millis(100));
+    let addr = serve_metrics(stats, "127.0.0.1:0").unwrap();
+    let _idle = TcpStream::connect(addr).unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("p2p_ping_rtt_mean_seconds{node=\"1\",peer=\"2\"} 0.1\n"));
+}
@@ -0,0 +1,142 @@
+use crate::{durations_quantile, PeerIdentifier, PeerSnapshot, Stats};
+
+/// Criterion peers are ranked by in [`Stats::rank_peers`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RankCriterion {
+    /// Lowest mean round trip time in seconds first
+    MeanRtt,
+    /// Lowest round trip time quantile in seconds first, e.g. `RttQuantile(0.99)`
+    RttQuantile(f64),
+    /// Highest byte-weighted mean transmission rate in bytes per second first
+    Throughput,
+    /// Lowest score first, where the score is the weighted sum of the mean round trip time
+    /// relative to the best peer and the best transmission rate relative to the peer's one.
+    /// The best possible score is `rtt_weight + throughput_weight`.
+    Composite {
+        rtt_weight: f64,
+        throughput_weight: f64,
+    },
+}
+
+impl<P: PeerIdentifier> Stats<P> {
+    /// Peers with their scores ordered from the best to the worst by `criterion`.
+    /// Peers with fewer samples than required by [`Stats::with_min_samples`] are left out.
+    pub fn rank_peers(&self, criterion: RankCriterion) -> Vec<(P, f64)> {
+        let min_samples = self.min_samples;
+        let peers = self.peer_snapshots(true);
+        let mean_rtt = |peer: &PeerSnapshot| {
+            peer.ping
+                .filter(|summary| summary.n_samples >= min_samples)
+                .map(|summary| summary.mean.as_secs_f64())
+        };
+        let throughput = |peer: &PeerSnapshot| {
+            peer.transmission
+                .filter(|summary| summary.n_samples >= min_samples)
+                .map(|summary| summary.mean)
+        };
+        let mut ranking: Vec<(P, f64)> = match criterion {
+            RankCriterion::MeanRtt => peers
+                .into_iter()
+                .filter_map(|(peer_id, peer)| Some((peer_id, mean_rtt(&peer)?)))
+                .collect(),
+            RankCriterion::RttQuantile(q) => peers
+                .into_iter()
+                .filter(|(_, peer)| mean_rtt(peer).is_some())
+                .filter_map(|(peer_id, peer)| {
+                    let value = durations_quantile(peer.pings.as_ref()?, q)?;
+                    Some((peer_id, value.as_secs_f64()))
+                })
+                .collect(),
+            RankCriterion::Throughput => peers
+                .into_iter()
+                .filter_map(|(peer_id, peer)| Some((peer_id, throughput(&peer)?)))
+                .collect(),
+            RankCriterion::Composite {
+                rtt_weight,
+                throughput_weight,
+            } => {
+                let metrics: Vec<(P, Option<f64>, Option<f64>)> = peers
+                    .into_iter()
+                    .map(|(peer_id, peer)| (peer_id, mean_rtt(&peer), throughput(&peer)))
+                    .filter(|(_, rtt, rate)| {
+                        (rtt_weight == 0.0 || rtt.is_some())
+                            && (throughput_weight == 0.0 || rate.is_some())
+                    })
+                    .collect();
+                let best_rtt = metrics
+                    .iter()
+                    .filter_map(|(_, rtt, _)| *rtt)
+                    .reduce(f64::min);
+                let best_rate = metrics
+                    .iter()
+                    .filter_map(|(_, _, rate)| *rate)
+                    .reduce(f64::max);
+                metrics
+                    .into_iter()
+                    .map(|(peer_id, rtt, rate)| {
+                        let rtt_score = match (rtt, best_rtt) {
+                            (Some(rtt), Some(best)) if best > 0.0 => rtt / best,
+                            _ => 1.0,
+                        };
+                        let rate_score = match (rate, best_rate) {
+                            (Some(rate), Some(best)) if rate > 0.0 => best / rate,
+                            _ => 1.0,
+                        };
+                        (
+                            peer_id,
+                            rtt_weight * rtt_score + throughput_weight * rate_score,
+                        )
+                    })
+                    .collect()
+            }
+        };
+        match criterion {
+            RankCriterion::Throughput => ranking.sort_by(|a, b| b.1.total_cmp(&a.1)),
+            _ => ranking.sort_by(|a, b| a.1.total_cmp(&b.1)),
+        }
+        ranking
+    }
+
+    /// Up to `k` best peers by `criterion`
+    pub fn best_peers(&self, k: usize, criterion: RankCriterion) -> Vec<P> {
+        self.rank_peers(criterion)
+            .into_iter()
+            .take(k)
+            .map(|(peer_id, _)| peer_id)
+            .collect()
+    }
+}
+
+#[test]
+fn correct_peer_ranking() {
+    use std::time::Duration;
+
+    let stats = Stats::new(100, "1".to_string()).with_min_samples(2);
+    for _ in 0..2 {
+        stats.add_ping("fast".to_string(), Duration::from_millis(10));
+        stats.add_ping("slow".to_string(), Duration::from_millis(40));
+        stats.add_transmission("fast".to_string(), Duration::from_secs(1), 100);
+        stats.add_transmission("slow".to_string(), Duration::from_secs(1), 400);
+    }
+    stats.add_ping("lucky".to_string(), Duration::from_millis(1));
+
+    assert_eq!(
+        stats.best_peers(1, RankCriterion::MeanRtt),
+        vec!["fast".to_string()]
+    );
+    assert_eq!(
+        stats.best_peers(5, RankCriterion::RttQuantile(0.99)),
+        vec!["fast".to_string(), "slow".to_string()]
+    );
+    assert_eq!(
+        stats.best_peers(5, RankCriterion::Throughput),
+        vec!["slow".to_string(), "fast".to_string()]
+    );
+    assert_eq!(
+        stats.rank_peers(RankCriterion::Composite {
+            rtt_weight: 2.0,
+            throughput_weight: 1.0,
+        }),
+        vec![("fast".to_string(), 6.0), ("slow".to_string(), 9.0)]
+    );
+}
@@ -0,0 +1,213 @@
+use crate::{
+    format::{format_timestamp, is_timestamp},
+    PeerIdentifier, ReportFormat, Stats,
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+/// Writes the report of [`Stats`] in a [`ReportFormat`] to a file periodically
+/// from a background thread.
+/// Each report is written to a file named with a suffix of its write time, which `path` then
+/// links to, keeping at most `max_files` previous reports. A final report is written on drop.
+pub struct StatsReporter<P: PeerIdentifier + Send + Sync + 'static = String> {
+    stats: Arc<Stats<P>>,
+    path: PathBuf,
+    format: ReportFormat,
+    max_files: usize,
+    shutdown: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<P: PeerIdentifier + Send + Sync + 'static> StatsReporter<P> {
+    /// Starts writing the report of `stats` in `format` to `path` every `interval`
+    pub fn new(
+        stats: Arc<Stats<P>>,
+        path: impl Into<PathBuf>,
+        format: ReportFormat,
+        interval: Duration,
+        max_files: usize,
+    ) -> Self {
+        let path = path.into();
+        let (shutdown, shutdown_received) = mpsc::channel();
+        let thread = {
+            let stats = Arc::clone(&stats);
+            let path = path.clone();
+            thread::spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = shutdown_received.recv_timeout(interval)
+                {
+                    // A failing write must not stop the reporter
+                    let _ = write_report(&stats, &path, format, max_files);
+                }
+            })
+        };
+        Self {
+            stats,
+            path,
+            format,
+            max_files,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn stats(&self) -> &Arc<Stats<P>> {
+        &self.stats
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn format(&self) -> ReportFormat {
+        self.format
+    }
+
+    /// Stops the background thread and writes the final report
+    pub fn shutdown(mut self) -> io::Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        match (self.shutdown.take(), self.thread.take()) {
+            (Some(shutdown), Some(thread)) => {
+                drop(shutdown);
+                let _ = thread.join();
+                write_report(&self.stats, &self.path, self.format, self.max_files)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<P: PeerIdentifier + Send + Sync + 'static> Drop for StatsReporter<P> {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+fn write_report<P: PeerIdentifier>(
+    stats: &Stats<P>,
+    path: &Path,
+    format: ReportFormat,
+    max_files: usize,
+) -> io::Result<()> {
+    if max_files == 0 {
+        return stats.save_as(utf8(path)?, format);
+    }
+    let mut stamped = path.as_os_str().to_owned();
+    stamped.push(format!(".{}", format_timestamp(SystemTime::now())));
+    let stamped = PathBuf::from(stamped);
+    stats.save_as(utf8(&stamped)?, format)?;
+    // Replace the current report by a link to the new one, so that readers never find it missing
+    let mut link = stamped.clone().into_os_string();
+    link.push(".tmp");
+    let _ = fs::remove_file(&link);
+    fs::hard_link(&stamped, &link).or_else(|_| fs::copy(&stamped, &link).map(drop))?;
+    fs::rename(&link, path)?;
+    // The newest stamped report is the current one
+    remove_oldest_rotated(path, max_files + 1)
+}
+
+fn utf8(path: &Path) -> io::Result<&str> {
+    path.to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Non UTF-8 report path"))
+}
+
+/// Removes rotated copies of `path` except the newest `max_files`
+fn remove_oldest_rotated(path: &Path, max_files: usize) -> io::Result<()> {
+    let prefix = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => format!("{}.", name),
+        None => return Ok(()),
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut rotated: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix))
+                .is_some_and(is_timestamp)
+        })
+        .map(|entry| entry.path())
+        .collect();
+    // Timestamps sort chronologically as strings
+    rotated.sort();
+    let n_excess = rotated.len().saturating_sub(max_files);
+    for old in &rotated[..n_excess] {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn reporter_rotates_and_flushes_on_drop() {
+    let dir = std::env::temp_dir().join(format!("p2p_node_stats_reporter_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("stats.txt");
+    let stats = Arc::new(Stats::new(100, "1".to_string()));
+    let reporter = StatsReporter::new(
+        Arc::clone(&stats),
+        &path,
+        ReportFormat::Text,
+        Duration::from_secs(3600),
+        2,
+    );
+    for peer in ["2", "3", "4"] {
+        stats.add_ping(peer.to_string(), Duration::from_millis(100));
+        write_report(&stats, &path, ReportFormat::Text, 2).unwrap();
+        // Keep the millisecond timestamps of the reports distinct
+        thread::sleep(Duration::from_millis(2));
+    }
+    stats.add_ping("5".to_string(), Duration::from_millis(100));
+    drop(reporter);
+
+    let report = fs::read_to_string(&path).unwrap();
+    let mut stamped: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|file| *file != path)
+        .collect();
+    stamped.sort();
+    let reports: Vec<String> = stamped
+        .iter()
+        .map(|file| fs::read_to_string(file).unwrap())
+        .collect();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(reports.len(), 3);
+    assert!(report.contains("\"5\" 100ms"));
+    assert_eq!(reports[2], report);
+    assert!(reports[1].contains("\"4\" 100ms") && !reports[1].contains("\"5\""));
+    assert!(reports[0].contains("\"3\" 100ms") && !reports[0].contains("\"4\""));
+}
+
+#[test]
+fn reporter_writes_in_its_format() {
+    let path = std::env::temp_dir().join(format!("p2p_node_stats_report_{}", std::process::id()));
+    let stats = Arc::new(Stats::new(100, "1".to_string()));
+    stats.add_ping("2".to_string(), Duration::from_millis(100));
+    let reporter = StatsReporter::new(
+        stats,
+        &path,
+        ReportFormat::SummaryCsv,
+        Duration::from_secs(60),
+        0,
+    );
+    reporter.shutdown().unwrap();
+
+    let report = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(report.starts_with("peer,"));
+    assert!(report.contains("\n2,0.1,"));
+}
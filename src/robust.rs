@@ -0,0 +1,141 @@
+use crate::{durations_quantile, quantile, Transmission};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Scale making the median absolute deviation a consistent estimator
+/// of the standard deviation for normally distributed samples
+const MAD_SCALE: f64 = 1.4826;
+
+/// Rule deciding which samples are excluded from the mean and its confidence interval
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum OutlierPolicy {
+    /// Use all samples
+    #[default]
+    Keep,
+    /// Exclude samples outside of `[Q1 - k * IQR, Q3 + k * IQR]`, `k` is usually `1.5`
+    Tukey { k: f64 },
+    /// Exclude samples further than `threshold` scaled median absolute deviations
+    /// from the median, `threshold` is usually `3.0`. Nothing is excluded when the MAD is zero.
+    Mad { threshold: f64 },
+}
+
+impl OutlierPolicy {
+    /// Samples which are not outliers, in their original order
+    pub fn retain(&self, durations: &[Duration]) -> Vec<Duration> {
+        let secs: Vec<f64> = durations.iter().map(Duration::as_secs_f64).collect();
+        match self.bounds(&secs) {
+            Some((low, high)) => durations
+                .iter()
+                .filter(|duration| (low..=high).contains(&duration.as_secs_f64()))
+                .copied()
+                .collect(),
+            None => durations.to_vec(),
+        }
+    }
+
+    /// Transmissions whose rate is not an outlier, in their original order
+    pub fn retain_transmissions(&self, transmissions: &[Transmission]) -> Vec<Transmission> {
+        let rates: Vec<f64> = transmissions.iter().map(Transmission::rate).collect();
+        match self.bounds(&rates) {
+            Some((low, high)) => transmissions
+                .iter()
+                .filter(|transmission| (low..=high).contains(&transmission.rate()))
+                .copied()
+                .collect(),
+            None => transmissions.to_vec(),
+        }
+    }
+
+    /// Range of the values which are not outliers, `None` when nothing is excluded
+    fn bounds(&self, values: &[f64]) -> Option<(f64, f64)> {
+        match *self {
+            OutlierPolicy::Keep => None,
+            OutlierPolicy::Tukey { k } => {
+                let (q1, q3) = (quantile(values, 0.25)?, quantile(values, 0.75)?);
+                let iqr = q3 - q1;
+                Some((q1 - k * iqr, q3 + k * iqr))
+            }
+            OutlierPolicy::Mad { threshold } => {
+                let (median, mad) = (median(values)?, mad(values)?);
+                if mad == 0.0 {
+                    return None;
+                }
+                let spread = threshold * MAD_SCALE * mad;
+                Some((median - spread, median + spread))
+            }
+        }
+    }
+}
+
+pub(crate) fn median(values: &[f64]) -> Option<f64> {
+    quantile(values, 0.5)
+}
+
+/// Median absolute deviation of values from their median
+pub(crate) fn mad(values: &[f64]) -> Option<f64> {
+    let median = median(values)?;
+    let deviations: Vec<f64> = values.iter().map(|value| (value - median).abs()).collect();
+    self::median(&deviations)
+}
+
+pub fn durations_median(durations: &[Duration]) -> Option<Duration> {
+    durations_quantile(durations, 0.5)
+}
+
+/// Median absolute deviation of durations from their median
+pub fn durations_mad(durations: &[Duration]) -> Option<Duration> {
+    let median = durations_median(durations)?;
+    let deviations: Vec<Duration> = durations
+        .iter()
+        .map(|duration| duration.abs_diff(median))
+        .collect();
+    durations_median(&deviations)
+}
+
+#[test]
+fn correct_durations_mad() {
+    let durations: Vec<Duration> = [1, 1, 2, 2, 4, 6, 9]
+        .iter()
+        .map(|secs| Duration::from_secs(*secs))
+        .collect();
+    assert_eq!(durations_median(&durations), Some(Duration::from_secs(2)));
+    assert_eq!(durations_mad(&durations), Some(Duration::from_secs(1)));
+    assert_eq!(durations_mad(&[]), None);
+}
+
+#[test]
+fn correct_outlier_policies() {
+    let mut durations: Vec<Duration> = (10..20).map(Duration::from_millis).collect();
+    durations.push(Duration::from_secs(5));
+    let tukey = OutlierPolicy::Tukey { k: 1.5 }.retain(&durations);
+    assert_eq!(tukey.len(), 10);
+    assert!(!tukey.contains(&Duration::from_secs(5)));
+    let mad = OutlierPolicy::Mad { threshold: 3.0 }.retain(&durations);
+    assert_eq!(mad.len(), 10);
+    assert_eq!(OutlierPolicy::Keep.retain(&durations).len(), 11);
+}
+
+#[test]
+fn outlier_policies_apply_to_rates() {
+    let mut transmissions: Vec<Transmission> = (10..20)
+        .map(|n_bytes| Transmission {
+            n_bytes,
+            elapsed: Duration::from_secs(1),
+        })
+        .collect();
+    transmissions.push(Transmission {
+        n_bytes: 5000,
+        elapsed: Duration::from_secs(1),
+    });
+    let tukey = OutlierPolicy::Tukey { k: 1.5 }.retain_transmissions(&transmissions);
+    assert_eq!(tukey.len(), 10);
+    assert!(tukey.iter().all(|transmission| transmission.n_bytes < 20));
+    let mad = OutlierPolicy::Mad { threshold: 3.0 }.retain_transmissions(&transmissions);
+    assert_eq!(mad.len(), 10);
+    assert_eq!(
+        OutlierPolicy::Keep
+            .retain_transmissions(&transmissions)
+            .len(),
+        11
+    );
+}
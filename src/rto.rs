@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Timeout used before any round trip time was measured, see RFC 6298 section 2.1
+pub const INITIAL_RTO: Duration = Duration::from_secs(1);
+
+/// Clock granularity `G` of RFC 6298 section 2
+pub const CLOCK_GRANULARITY: Duration = Duration::from_millis(1);
+
+/// Bounds the recommended retransmission timeout is clamped to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeoutBounds {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl Default for TimeoutBounds {
+    /// Bounds recommended by RFC 6298 sections 2.4 and 2.5
+    fn default() -> Self {
+        Self {
+            min: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+        }
+    }
+}
+
+impl TimeoutBounds {
+    pub fn clamp(&self, timeout: Duration) -> Duration {
+        timeout.max(self.min).min(self.max)
+    }
+}
+
+/// Smoothed round trip time estimator as specified in RFC 6298
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RttEstimator {
+    srtt: Duration,
+    rttvar: Duration,
+}
+
+impl RttEstimator {
+    /// Initializes the estimator with the first measurement, see RFC 6298 section 2.2
+    pub fn new(rtt: Duration) -> Self {
+        Self {
+            srtt: rtt,
+            rttvar: rtt / 2,
+        }
+    }
+
+    /// Updates the estimator with a subsequent measurement, see RFC 6298 section 2.3
+    pub fn update(&mut self, rtt: Duration) {
+        self.rttvar = (self.rttvar * 3 + self.srtt.abs_diff(rtt)) / 4;
+        self.srtt = (self.srtt * 7 + rtt) / 8;
+    }
+
+    /// Smoothed round trip time
+    pub fn srtt(&self) -> Duration {
+        self.srtt
+    }
+
+    /// Round trip time variation
+    pub fn rttvar(&self) -> Duration {
+        self.rttvar
+    }
+
+    /// Retransmission timeout `SRTT + max(G, 4 * RTTVAR)` clamped to `bounds`
+    pub fn rto(&self, bounds: &TimeoutBounds) -> Duration {
+        bounds.clamp(self.srtt + CLOCK_GRANULARITY.max(self.rttvar * 4))
+    }
+}
+
+#[test]
+fn correct_rtt_estimator() {
+    let mut estimator = RttEstimator::new(Duration::from_millis(800));
+    assert_eq!(estimator.srtt(), Duration::from_millis(800));
+    assert_eq!(estimator.rttvar(), Duration::from_millis(400));
+    estimator.update(Duration::from_millis(400));
+    assert_eq!(estimator.srtt(), Duration::from_millis(750));
+    assert_eq!(estimator.rttvar(), Duration::from_millis(400));
+    let bounds = TimeoutBounds::default();
+    assert_eq!(estimator.rto(&bounds), Duration::from_millis(2350));
+    let tight = TimeoutBounds {
+        min: Duration::from_millis(100),
+        max: Duration::from_secs(2),
+    };
+    assert_eq!(estimator.rto(&tight), Duration::from_secs(2));
+}
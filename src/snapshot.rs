@@ -0,0 +1,167 @@
+use crate::{
+    atomic::write_atomic, ChangePoint, LossSummary, PeerIdentifier, Stats, Summary,
+    ThroughputSummary, Transmission,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Statistics of a node at a point in time, which can be exported as JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub node_id: String,
+    pub taken_at: SystemTime,
+    /// Peers sorted by their ids
+    pub peers: Vec<PeerSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerSnapshot {
+    pub peer_id: String,
+    pub ping: Option<Summary>,
+    pub transmission: Option<ThroughputSummary>,
+    #[serde(default)]
+    pub loss: Option<LossSummary>,
+    /// Raw round trip times in the window, from the oldest to the newest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pings: Option<Vec<Duration>>,
+    /// Raw transmissions in the window, from the oldest to the newest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transmissions: Option<Vec<Transmission>>,
+    /// Latest detected level shifts, from the oldest to the newest
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub change_points: Vec<ChangePoint>,
+}
+
+impl PeerSnapshot {
+    fn new(peer_id: String) -> Self {
+        Self {
+            peer_id,
+            ping: None,
+            transmission: None,
+            loss: None,
+            pings: None,
+            transmissions: None,
+            change_points: Vec::new(),
+        }
+    }
+}
+
+impl StatsSnapshot {
+    pub fn peer(&self, peer_id: &str) -> Option<&PeerSnapshot> {
+        self.peers.iter().find(|peer| peer.peer_id == peer_id)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn save_json(&self, filename: &str) -> io::Result<()> {
+        write_atomic(filename, |file| {
+            Ok(serde_json::to_writer_pretty(file, self)?)
+        })
+    }
+
+    pub fn load_json(filename: &str) -> io::Result<Self> {
+        let file = File::open(filename)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+}
+
+impl<P: PeerIdentifier> Stats<P> {
+    /// Takes a snapshot of the current windows, including raw samples if `include_raw` is set
+    pub fn snapshot(&self, include_raw: bool) -> StatsSnapshot {
+        StatsSnapshot {
+            node_id: self.peer_id.to_string(),
+            taken_at: SystemTime::now(),
+            peers: self
+                .peer_snapshots(include_raw)
+                .into_iter()
+                .map(|(_, snapshot)| snapshot)
+                .collect(),
+        }
+    }
+
+    /// Snapshots of all peers together with their ids, sorted by the displayed ids
+    pub(crate) fn peer_snapshots(&self, include_raw: bool) -> Vec<(P, PeerSnapshot)> {
+        self.prune();
+        let now = Instant::now();
+        let mut peers = HashMap::new();
+        for (peer, mut window) in self.pings_to_peers.clone() {
+            window.expire(now);
+            let pings = window.to_vec();
+            let jitter = self
+                .jitter_estimators
+                .get(&peer)
+                .map(|estimator| estimator.jitter());
+            let snapshot = peers
+                .entry(peer.clone())
+                .or_insert_with(|| PeerSnapshot::new(peer.to_string()));
+            snapshot.ping = self
+                .summarize(&pings)
+                .map(|summary| Summary { jitter, ..summary });
+            if include_raw {
+                snapshot.pings = Some(pings);
+            }
+        }
+        for (peer, mut window) in self.ping_outcomes.clone() {
+            window.expire(now);
+            let snapshot = peers
+                .entry(peer.clone())
+                .or_insert_with(|| PeerSnapshot::new(peer.to_string()));
+            snapshot.loss = LossSummary::from_outcomes(&window.to_vec(), self.confidence);
+        }
+        for (peer, mut window) in self.transmissions_rates.clone() {
+            window.expire(now);
+            let transmissions = window.to_vec();
+            let snapshot = peers
+                .entry(peer.clone())
+                .or_insert_with(|| PeerSnapshot::new(peer.to_string()));
+            snapshot.transmission = self.summarize_transmissions(&transmissions);
+            if include_raw {
+                snapshot.transmissions = Some(transmissions);
+            }
+        }
+        for (peer, change_points) in self.change_points.clone() {
+            let snapshot = peers
+                .entry(peer.clone())
+                .or_insert_with(|| PeerSnapshot::new(peer.to_string()));
+            snapshot.change_points = change_points.into_iter().collect();
+        }
+        let mut peers: Vec<(P, PeerSnapshot)> = peers.into_iter().collect();
+        peers.sort_by(|(_, a), (_, b)| a.peer_id.cmp(&b.peer_id));
+        peers
+    }
+}
+
+#[test]
+fn snapshot_json_round_trip() {
+    let stats = Stats::new(100, "1".to_string());
+    stats.add_ping("2".to_string(), Duration::from_millis(10));
+    stats.add_ping("2".to_string(), Duration::from_millis(30));
+    stats.add_transmission("3".to_string(), Duration::from_secs(1), 1000);
+
+    let snapshot = stats.snapshot(true);
+    assert_eq!(snapshot.node_id, "1");
+    assert_eq!(snapshot.peers.len(), 2);
+    let peer_2 = snapshot.peer("2").unwrap();
+    assert_eq!(peer_2.ping.unwrap().mean, Duration::from_millis(20));
+    assert_eq!(peer_2.pings.as_ref().unwrap().len(), 2);
+    assert!(peer_2.transmission.is_none());
+
+    let json = snapshot.to_json().unwrap();
+    assert_eq!(StatsSnapshot::from_json(&json).unwrap(), snapshot);
+    assert!(!stats
+        .snapshot(false)
+        .to_json()
+        .unwrap()
+        .contains("\"pings\""));
+}
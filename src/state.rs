@@ -0,0 +1,299 @@
+use crate::{
+    atomic::write_atomic, ChangePoint, JitterEstimator, PeerIdentifier, RttEstimator, Sample,
+    Stats, Transmission, Window,
+};
+use chashmap::CHashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, prelude::*, BufReader},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// First line of a state file, followed by the format version
+const STATE_HEADER: &str = "p2p-node-stats-state";
+const STATE_VERSION: u32 = 1;
+
+/// Lossless representation of [`Stats`] used to warm-start a node after a restart.
+/// Fields unknown to this version are ignored when loading.
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    peer_id: String,
+    window_size: usize,
+    max_age: Option<Duration>,
+    pings_to_peers: BTreeMap<String, Vec<SavedSample<Duration>>>,
+    #[serde(default)]
+    ping_outcomes: BTreeMap<String, Vec<SavedSample<bool>>>,
+    transmissions_rates: BTreeMap<String, Vec<SavedSample<Transmission>>>,
+    rtt_estimators: BTreeMap<String, RttEstimator>,
+    #[serde(default)]
+    jitter_estimators: BTreeMap<String, JitterEstimator>,
+    #[serde(default)]
+    last_seen: BTreeMap<String, SystemTime>,
+    #[serde(default)]
+    change_points: BTreeMap<String, Vec<ChangePoint>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedSample<T> {
+    value: T,
+    taken_at: SystemTime,
+}
+
+impl<P: PeerIdentifier> Stats<P> {
+    /// Saves all windows and estimators, so that they can be restored by [`Stats::load_from_file`].
+    /// Peer ids are saved with their `Display` implementation.
+    pub fn save_state_to_file(&self, filename: &str) -> io::Result<()> {
+        self.prune();
+        let state = self.saved_state();
+        write_atomic(filename, |file| {
+            writeln!(file, "{} {}", STATE_HEADER, STATE_VERSION)?;
+            Ok(serde_json::to_writer(file, &state)?)
+        })
+    }
+
+    fn saved_state(&self) -> SavedState {
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+        SavedState {
+            peer_id: self.peer_id.to_string(),
+            window_size: self.window_size,
+            max_age: self.max_age,
+            pings_to_peers: save_windows(&self.pings_to_peers),
+            ping_outcomes: save_windows(&self.ping_outcomes),
+            transmissions_rates: save_windows(&self.transmissions_rates),
+            rtt_estimators: save_map(&self.rtt_estimators, |estimator| estimator),
+            jitter_estimators: save_map(&self.jitter_estimators, |estimator| estimator),
+            last_seen: save_map(&self.last_seen, |at| {
+                wall_now - now.saturating_duration_since(at)
+            }),
+            change_points: save_map(&self.change_points, |change_points| {
+                change_points.into_iter().collect()
+            }),
+        }
+    }
+
+    fn restore_window<T>(
+        &self,
+        samples: Vec<SavedSample<T>>,
+        now: Instant,
+        wall_now: SystemTime,
+    ) -> Window<T> {
+        let mut window = self.new_window();
+        for sample in samples {
+            let age = wall_now.duration_since(sample.taken_at).unwrap_or_default();
+            window.push_sample(Sample {
+                value: sample.value,
+                at: saturating_instant_sub(now, age),
+                taken_at: sample.taken_at,
+            });
+        }
+        window.expire(now);
+        window
+    }
+}
+
+impl<P: PeerIdentifier + FromStr> Stats<P> {
+    /// Restores stats saved by [`Stats::save_state_to_file`], parsing peer ids with `FromStr`.
+    /// Other settings like report quantiles are not saved and should be set again.
+    pub fn load_from_file(filename: &str) -> io::Result<Self> {
+        Self::from_saved_state(read_saved_state(filename)?)
+    }
+
+    /// Restores stats like [`Stats::load_from_file`], but ignores the saved max age so that
+    /// no sample expires, however old the file is. Useful to inspect a saved node.
+    pub fn load_all_from_file(filename: &str) -> io::Result<Self> {
+        let state = read_saved_state(filename)?;
+        Self::from_saved_state(SavedState {
+            max_age: None,
+            ..state
+        })
+    }
+
+    fn from_saved_state(state: SavedState) -> io::Result<Self> {
+        let mut stats = Self::new(state.window_size, parse_peer_id(&state.peer_id)?);
+        stats.max_age = state.max_age;
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+        for (peer, samples) in state.pings_to_peers {
+            insert_peer(
+                &stats.pings_to_peers,
+                &peer,
+                stats.restore_window(samples, now, wall_now),
+            )?;
+        }
+        for (peer, samples) in state.ping_outcomes {
+            insert_peer(
+                &stats.ping_outcomes,
+                &peer,
+                stats.restore_window(samples, now, wall_now),
+            )?;
+        }
+        for (peer, samples) in state.transmissions_rates {
+            insert_peer(
+                &stats.transmissions_rates,
+                &peer,
+                stats.restore_window(samples, now, wall_now),
+            )?;
+        }
+        for (peer, estimator) in state.rtt_estimators {
+            insert_peer(&stats.rtt_estimators, &peer, estimator)?;
+        }
+        for (peer, estimator) in state.jitter_estimators {
+            insert_peer(&stats.jitter_estimators, &peer, estimator)?;
+        }
+        for (peer, change_points) in state.change_points {
+            insert_peer(&stats.change_points, &peer, change_points.into())?;
+        }
+        for (peer, at) in state.last_seen {
+            let age = wall_now.duration_since(at).unwrap_or_default();
+            stats.touch_peer(&parse_peer_id(&peer)?, saturating_instant_sub(now, age));
+        }
+        Ok(stats)
+    }
+}
+
+fn read_saved_state(filename: &str) -> io::Result<SavedState> {
+    let mut file = BufReader::new(File::open(filename)?);
+    let mut header = String::new();
+    file.read_line(&mut header)?;
+    let version = match header.trim_end().split_once(' ') {
+        Some((STATE_HEADER, version)) => version.parse::<u32>().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Not a stats state file"))?;
+    if version > STATE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported stats state version {}", version),
+        ));
+    }
+    Ok(serde_json::from_reader(file)?)
+}
+
+pub(crate) fn parse_peer_id<P: FromStr>(peer_id: &str) -> io::Result<P> {
+    peer_id.parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid peer id {:?}", peer_id),
+        )
+    })
+}
+
+/// Inserts the value of a saved peer, which must not collide with a peer inserted before,
+/// e.g. `1` and `01` for numeric ids
+fn insert_peer<P: PeerIdentifier + FromStr, V>(
+    map: &CHashMap<P, V>,
+    peer_id: &str,
+    value: V,
+) -> io::Result<()> {
+    match map.insert(parse_peer_id(peer_id)?, value) {
+        Some(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Duplicate peer id {:?}", peer_id),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn save_map<P: PeerIdentifier, V: Clone, S>(
+    map: &CHashMap<P, V>,
+    save: impl Fn(V) -> S,
+) -> BTreeMap<String, S> {
+    map.clone()
+        .into_iter()
+        .map(|(peer, value)| (peer.to_string(), save(value)))
+        .collect()
+}
+
+fn save_windows<P: PeerIdentifier, T: Clone>(
+    map: &CHashMap<P, Window<T>>,
+) -> BTreeMap<String, Vec<SavedSample<T>>> {
+    save_map(map, |window| {
+        window
+            .samples()
+            .map(|sample| SavedSample {
+                value: sample.value.clone(),
+                taken_at: sample.taken_at,
+            })
+            .collect()
+    })
+}
+
+/// `now - age`, or the earliest representable instant when the monotonic clock
+/// started less than `age` ago, e.g. after a reboot
+pub(crate) fn saturating_instant_sub(now: Instant, age: Duration) -> Instant {
+    if let Some(instant) = now.checked_sub(age) {
+        return instant;
+    }
+    let mut earliest = now;
+    let mut remaining = age;
+    let mut step = age;
+    while !step.is_zero() && !remaining.is_zero() {
+        let chunk = step.min(remaining);
+        match earliest.checked_sub(chunk) {
+            Some(instant) => {
+                earliest = instant;
+                remaining -= chunk;
+            }
+            None => step = chunk / 2,
+        }
+    }
+    earliest
+}
+
+#[test]
+fn state_round_trip() {
+    let path = std::env::temp_dir().join(format!("p2p_node_stats_state_{}", std::process::id()));
+    let filename = path.to_str().unwrap();
+    let stats = Stats::new(2, "1".to_string()).with_max_age(Duration::from_secs(3600));
+    stats.add_ping("2".to_string(), Duration::from_millis(10));
+    stats.add_ping("2".to_string(), Duration::from_millis(20));
+    stats.add_ping("2".to_string(), Duration::from_millis(30));
+    stats.add_ping_timeout("2".to_string());
+    stats.add_transmission("3".to_string(), Duration::from_secs(1), 1000);
+    stats.save_state_to_file(filename).unwrap();
+
+    let restored: Stats = Stats::load_from_file(filename).unwrap();
+    std::fs::remove_file(filename).unwrap();
+    assert_eq!(restored.peer_id, "1");
+    assert_eq!(restored.window_size, 2);
+    assert_eq!(restored.max_age, Some(Duration::from_secs(3600)));
+    assert_eq!(restored.ping_summary("2"), stats.ping_summary("2"));
+    assert_eq!(
+        restored.transmission_summary("3"),
+        stats.transmission_summary("3")
+    );
+    assert_eq!(restored.rtt_estimate("2"), stats.rtt_estimate("2"));
+    assert_eq!(restored.ping_loss("2"), stats.ping_loss("2"));
+    assert_eq!(restored.ping_jitter("2"), stats.ping_jitter("2"));
+}
+
+#[test]
+fn rejects_newer_state_version() {
+    let path = std::env::temp_dir().join(format!("p2p_node_stats_version_{}", std::process::id()));
+    let filename = path.to_str().unwrap();
+    std::fs::write(
+        filename,
+        format!("{} {}\n{{}}", STATE_HEADER, STATE_VERSION + 1),
+    )
+    .unwrap();
+    let error = Stats::<String>::load_from_file(filename).err().unwrap();
+    std::fs::remove_file(filename).unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn rejects_duplicate_peers() {
+    let path = std::env::temp_dir().join(format!("p2p_node_stats_dup_{}", std::process::id()));
+    let filename = path.to_str().unwrap();
+    let stats = Stats::new(10, "1".to_string());
+    stats.add_ping("1".to_string(), Duration::from_millis(10));
+    stats.add_ping("01".to_string(), Duration::from_millis(20));
+    stats.save_state_to_file(filename).unwrap();
+    let error = Stats::<u32>::load_from_file(filename).err().unwrap();
+    std::fs::remove_file(filename).unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
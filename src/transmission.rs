@@ -0,0 +1,200 @@
+use crate::{
+    quantile,
+    robust::{mad, median},
+    ConfidenceLevel, OutlierPolicy,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Single data transmission to a peer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transmission {
+    pub n_bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Transmission {
+    /// Transmission rate in bytes per second
+    pub fn rate(&self) -> f64 {
+        self.n_bytes as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Transmissions of zero bytes or taking zero time carry no rate information
+    pub fn is_measurable(&self) -> bool {
+        self.n_bytes > 0 && !self.elapsed.is_zero()
+    }
+}
+
+/// Transmission rate statistics in bytes per second, where every transmission
+/// is weighted by its number of bytes.
+/// The mean, standard deviation and interval leave out transmissions whose rate is an outlier.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThroughputSummary {
+    pub mean: f64,
+    pub std_dev: f64,
+    /// Half-width of the confidence interval of the mean, unknown for less than 2 transmissions
+    pub error: Option<f64>,
+    #[serde(default)]
+    pub confidence: ConfidenceLevel,
+    /// Number of transmissions in the window, including outliers
+    pub n_samples: usize,
+    #[serde(default)]
+    pub n_outliers: usize,
+    /// Number of bytes in the window, including outliers
+    pub n_bytes: u64,
+    pub min: f64,
+    pub max: f64,
+    /// Unweighted median of the rates
+    #[serde(default)]
+    pub median: f64,
+    /// Median absolute deviation of the rates from their median
+    #[serde(default)]
+    pub mad: f64,
+}
+
+impl ThroughputSummary {
+    pub fn from_transmissions(
+        transmissions: &[Transmission],
+        confidence: ConfidenceLevel,
+        outlier_policy: OutlierPolicy,
+    ) -> Option<Self> {
+        let retained = outlier_policy.retain_transmissions(transmissions);
+        let rates: Vec<f64> = transmissions.iter().map(Transmission::rate).collect();
+        Some(Self {
+            mean: transmissions_mean_rate(&retained)?,
+            std_dev: transmissions_rate_std_dev(&retained)?,
+            error: transmissions_rate_error_with_ci(&retained, confidence),
+            confidence,
+            n_samples: transmissions.len(),
+            n_outliers: transmissions.len() - retained.len(),
+            n_bytes: transmissions.iter().map(|t| t.n_bytes).sum(),
+            min: rates.iter().copied().reduce(f64::min)?,
+            max: rates.iter().copied().reduce(f64::max)?,
+            median: median(&rates)?,
+            mad: mad(&rates)?,
+        })
+    }
+}
+
+#[test]
+fn throughput_summary_leaves_out_outliers() {
+    let mut transmissions: Vec<Transmission> = [100, 110, 90, 105, 95]
+        .iter()
+        .map(|&n_bytes| Transmission {
+            n_bytes,
+            elapsed: Duration::from_secs(1),
+        })
+        .collect();
+    transmissions.push(Transmission {
+        n_bytes: 100,
+        elapsed: Duration::from_millis(1),
+    });
+    let summary = ThroughputSummary::from_transmissions(
+        &transmissions,
+        ConfidenceLevel::P95,
+        OutlierPolicy::Mad { threshold: 3.0 },
+    )
+    .unwrap();
+    assert_eq!(summary.n_samples, 6);
+    assert_eq!(summary.n_outliers, 1);
+    assert_eq!(summary.n_bytes, 600);
+    assert_eq!(summary.mean, 100.0 + 250.0 / 500.0);
+    assert_eq!(summary.median, 102.5);
+    assert_eq!(summary.mad, 7.5);
+    assert_eq!(summary.max, 100_000.0);
+}
+
+/// Byte-weighted mean transmission rate in bytes per second
+pub fn transmissions_mean_rate(transmissions: &[Transmission]) -> Option<f64> {
+    let total_bytes: u64 = transmissions.iter().map(|t| t.n_bytes).sum();
+    if total_bytes == 0 {
+        None
+    } else {
+        Some(
+            transmissions
+                .iter()
+                .fold(0f64, |acc, t| acc + t.n_bytes as f64 * t.rate())
+                / total_bytes as f64,
+        )
+    }
+}
+
+#[test]
+fn correct_transmissions_mean_rate() {
+    let transmissions = vec![
+        Transmission {
+            n_bytes: 10,
+            elapsed: Duration::from_secs(1),
+        },
+        Transmission {
+            n_bytes: 30,
+            elapsed: Duration::from_secs(1),
+        },
+    ];
+    assert_eq!(transmissions_mean_rate(&transmissions), Some(25.0));
+    assert_eq!(transmissions_mean_rate(&[]), None);
+}
+
+/// Byte-weighted standard deviation of transmission rates in bytes per second
+pub fn transmissions_rate_std_dev(transmissions: &[Transmission]) -> Option<f64> {
+    let mean = transmissions_mean_rate(transmissions)?;
+    let total_bytes: u64 = transmissions.iter().map(|t| t.n_bytes).sum();
+    Some(
+        (transmissions.iter().fold(0f64, |acc, t| {
+            acc + t.n_bytes as f64 * (t.rate() - mean).powi(2)
+        }) / total_bytes as f64)
+            .sqrt(),
+    )
+}
+
+/// Half-width of the confidence interval of the byte-weighted mean rate, based on Student's
+/// t-distribution. Uses the Kish effective sample size `(Σw)² / Σw²` in place of the number
+/// of transmissions. There is no interval for less than `2` transmissions.
+pub fn transmissions_rate_error_with_ci(
+    transmissions: &[Transmission],
+    confidence: ConfidenceLevel,
+) -> Option<f64> {
+    if transmissions.len() < 2 {
+        return None;
+    }
+    let std_dev = transmissions_rate_std_dev(transmissions)?;
+    let (sum, sum_of_squares) = transmissions.iter().fold((0f64, 0f64), |(s, sq), t| {
+        (s + t.n_bytes as f64, sq + (t.n_bytes as f64).powi(2))
+    });
+    let n_effective = sum.powi(2) / sum_of_squares;
+    if n_effective <= 1.0 {
+        return None;
+    }
+    let t = confidence.t((n_effective.round() as usize).saturating_sub(1).max(1))?;
+    let sample_std_dev = std_dev * (n_effective / (n_effective - 1.0)).sqrt();
+    Some(t * sample_std_dev / n_effective.sqrt())
+}
+
+#[test]
+fn correct_transmissions_rate_error_with_ci() {
+    let transmissions = vec![
+        Transmission {
+            n_bytes: 100,
+            elapsed: Duration::from_secs(1),
+        },
+        Transmission {
+            n_bytes: 100,
+            elapsed: Duration::from_millis(500),
+        },
+    ];
+    let epsilon = 0.01;
+    let std_dev = transmissions_rate_std_dev(&transmissions).unwrap();
+    assert!((std_dev - 50.0).abs() < epsilon);
+    let error = transmissions_rate_error_with_ci(&transmissions, ConfidenceLevel::P95).unwrap();
+    assert!((error - 12.706 * 50.0).abs() < epsilon);
+    assert_eq!(
+        transmissions_rate_error_with_ci(&transmissions[..1], ConfidenceLevel::P95),
+        None
+    );
+}
+
+/// Quantile `q` in `[0, 1]` of transmission rates in bytes per second
+pub fn transmissions_rate_quantile(transmissions: &[Transmission], q: f64) -> Option<f64> {
+    let rates: Vec<f64> = transmissions.iter().map(Transmission::rate).collect();
+    quantile(&rates, q)
+}
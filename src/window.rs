@@ -0,0 +1,232 @@
+use std::{
+    collections::VecDeque,
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime},
+};
+
+pub trait PushLossy<T> {
+    fn push_lossy(&mut self, element: T, window_size: usize);
+}
+
+impl<T> PushLossy<T> for Vec<T> {
+    fn push_lossy(&mut self, element: T, window_size: usize) {
+        if self.len() >= window_size {
+            self.remove(0);
+        }
+        self.push(element);
+    }
+}
+
+impl<T> PushLossy<T> for VecDeque<T> {
+    fn push_lossy(&mut self, element: T, window_size: usize) {
+        if window_size == 0 {
+            return;
+        }
+        while self.len() >= window_size {
+            self.pop_front();
+        }
+        self.push_back(element);
+    }
+}
+
+#[test]
+fn correct_push_lossy() {
+    let mut vector = Vec::new();
+    vector.push_lossy(1, 2);
+    vector.push_lossy(2, 2);
+    vector.push_lossy(3, 2);
+    assert_eq!(vector, vec![2, 3]);
+}
+
+/// Value recorded in a window together with the time it was taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample<T> {
+    pub value: T,
+    /// Monotonic time, used for expiry
+    pub at: Instant,
+    /// Wall-clock time, used for time range queries
+    pub taken_at: SystemTime,
+}
+
+impl<T> Sample<T> {
+    /// Sample taken at the monotonic time `at`, with the wall-clock time derived from it
+    pub fn new(value: T, at: Instant) -> Self {
+        Self {
+            value,
+            at,
+            taken_at: wall_clock(at),
+        }
+    }
+}
+
+/// Wall-clock time corresponding to the monotonic time `at`, for samples given only an instant.
+/// Both clocks are paired once per process, so equal instants always map to equal times,
+/// but the result drifts from the real time after a suspend or a clock step.
+pub(crate) fn wall_clock(at: Instant) -> SystemTime {
+    static ANCHOR: OnceLock<(Instant, SystemTime)> = OnceLock::new();
+    let &(anchor, wall_anchor) = ANCHOR.get_or_init(|| (Instant::now(), SystemTime::now()));
+    if at <= anchor {
+        wall_anchor - anchor.duration_since(at)
+    } else {
+        wall_anchor + at.duration_since(anchor)
+    }
+}
+
+/// Ring buffer holding the latest `capacity` samples in insertion order.
+/// Storage grows with the samples, pushing to a full window drops the oldest one in constant time.
+/// Optionally samples older than `max_age` are expired as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window<T> {
+    samples: VecDeque<Sample<T>>,
+    capacity: usize,
+    max_age: Option<Duration>,
+}
+
+impl<T> Window<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::new(),
+            capacity,
+            max_age: None,
+        }
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.push_sample(Sample {
+            value,
+            at: Instant::now(),
+            taken_at: SystemTime::now(),
+        })
+    }
+
+    pub fn push_at(&mut self, value: T, at: Instant) {
+        self.push_sample(Sample::new(value, at))
+    }
+
+    /// Pushes a sample keeping both of its timestamps, e.g. when restoring saved samples
+    pub fn push_sample(&mut self, sample: Sample<T>) {
+        self.expire(sample.at);
+        self.samples.push_lossy(sample, self.capacity)
+    }
+
+    /// Drops samples which are older than `max_age` at the time `now`
+    pub fn expire(&mut self, now: Instant) {
+        while let Some(sample) = self.samples.front() {
+            if !self.is_outdated(sample.at, now) {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    fn is_outdated(&self, at: Instant, now: Instant) -> bool {
+        match self.max_age {
+            Some(max_age) => now.saturating_duration_since(at) > max_age,
+            None => false,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Iterates values from the oldest to the newest sample
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.samples.iter().map(|sample| &sample.value)
+    }
+
+    /// Iterates from the oldest to the newest sample
+    pub fn samples(&self) -> impl Iterator<Item = &Sample<T>> {
+        self.samples.iter()
+    }
+
+    /// Iterates samples taken from `from` to `to` inclusive by the wall clock
+    pub fn samples_between(
+        &self,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> impl Iterator<Item = &Sample<T>> {
+        self.samples
+            .iter()
+            .filter(move |sample| from <= sample.taken_at && sample.taken_at <= to)
+    }
+}
+
+impl<T: Clone> Window<T> {
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T> PushLossy<T> for Window<T> {
+    /// Pushes `element` ignoring `window_size`, as the window is bounded by its own capacity
+    fn push_lossy(&mut self, element: T, _window_size: usize) {
+        self.push(element)
+    }
+}
+
+#[test]
+fn correct_window_push() {
+    let mut window = Window::new(3);
+    for i in 1..=5 {
+        window.push(i);
+    }
+    assert_eq!(window.len(), 3);
+    assert_eq!(window.to_vec(), vec![3, 4, 5]);
+    assert_eq!(window.capacity(), 3);
+
+    let mut empty = Window::new(0);
+    empty.push(1);
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn correct_window_expire() {
+    let start = Instant::now();
+    let mut window = Window::new(10).with_max_age(Duration::from_secs(10));
+    window.push_at(1, start);
+    window.push_at(2, start + Duration::from_secs(5));
+    window.push_at(3, start + Duration::from_secs(12));
+    assert_eq!(window.to_vec(), vec![2, 3]);
+    window.expire(start + Duration::from_secs(20));
+    assert_eq!(window.to_vec(), vec![3]);
+}
+
+#[test]
+fn correct_window_samples_between() {
+    let start = Instant::now();
+    let wall_start = SystemTime::now();
+    let mut window = Window::new(10);
+    for i in 0..5 {
+        window.push_sample(Sample {
+            value: i,
+            at: start,
+            taken_at: wall_start + Duration::from_secs(i),
+        });
+    }
+    let values: Vec<u64> = window
+        .samples_between(
+            wall_start + Duration::from_secs(1),
+            wall_start + Duration::from_secs(3),
+        )
+        .map(|sample| sample.value)
+        .collect();
+    assert_eq!(values, vec![1, 2, 3]);
+}